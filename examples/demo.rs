@@ -14,6 +14,9 @@ use bevy::window::WindowMode;
 // Built-in Bevy plugins to print FPS to console.
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, PrintDiagnosticsPlugin};
 
+// For finding the camera, to move it over the map
+use bevy::render::camera::Camera;
+
 // Until we have our own keyboard handling, this is handy...
 use bevy::input::system::exit_on_esc_system;
 
@@ -21,7 +24,10 @@ use bevy::input::system::exit_on_esc_system;
 use rand::prelude::*;
 
 // This is ... the thing being demonstrated here :)
-use bevy_mapengine::{MapEngineConfig, MapEnginePlugin, MapSpace, MapSpaceRefreshNeeded};
+use bevy_mapengine::{
+    Map, MapEngineConfig, MapEnginePlugin, MapEngineState, MapSpace, MapSpaceClicked,
+    MapSpaceHovered, TileRegistry,
+};
 
 /*----------------------------------------------------------------------------*/
 
@@ -35,10 +41,42 @@ fn setup_camera_system(commands: &mut Commands) {
 /// This is a one-time system that spawns some MapSpace components.
 /// For a future phase of this demo we'll need something more sophisticated,
 /// but this works for now. It needs Commands to do the spawning, and the
-/// AssetServer resource to get the handles for textures by name.
+/// TileRegistry resource to get the handles for textures by name.
+///
+/// The TileRegistry is only filled in once the engine is Running, so
+/// this runs every frame until then, and uses a Local resource (one
+/// private to this system) to remember that it's done.
 // FUTURE Maybe parse a text file or multi-line string with character
 // representations of the map?
-fn setup_demo_map_system(commands: &mut Commands, asset_server: Res<AssetServer>) {
+fn setup_demo_map_system(
+    commands: &mut Commands,
+    mapengine_state: Res<State<MapEngineState>>,
+    mut tile_registry: ResMut<TileRegistry>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut mapengine_map: ResMut<Map>,
+    mut done: Local<bool>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    if *done || !matches!(mapengine_state.current(), MapEngineState::Running) {
+        return;
+    }
+    *done = true;
+
+    // Tiles don't have to come from files. Here, we make a dry, yellowed
+    // version of grass1 by fiddling with its pixels, and add it as a
+    // new tile called "dry_grass".
+    let mut dry_grass = textures
+        .get(&tile_registry.handle("grass1").unwrap())
+        .unwrap()
+        .clone();
+    for pixel in dry_grass.data.chunks_mut(4) {
+        pixel[0] = pixel[0].saturating_add(60);
+        pixel[2] /= 2;
+    }
+    tile_registry
+        .register("dry_grass", dry_grass, &mut textures, &mut mapengine_map)
+        .unwrap();
+
     // We're going to put down a bunch of stuff at random, so we
     // will need a random number generator. And, for this demo,
     // rather than the default random number generator which starts
@@ -48,31 +86,72 @@ fn setup_demo_map_system(commands: &mut Commands, asset_server: Res<AssetServer>
     // Use the following instead if you need an actual random game.
     //let mut rng = rand::thread_rng();
 
-    for row in 0..12 {
-        for col in 0..20 {
+    const COLS: i32 = 20;
+    const ROWS: i32 = 12;
+    for row in 0..ROWS {
+        for col in 0..COLS {
             // Most likely to just be grass, but throw in some
             // trees as well.
             let tile_type = match rng.gen_range(0..50) {
-                0 => "terrain/tree6.png",
-                1 => "terrain/pine6.png",
-                2..=3 => "terrain/tree3.png",
-                4..=5 => "terrain/pine3.png",
-                6..=8 => "terrain/tree2.png",
-                9..=11 => "terrain/pine2.png",
-                12..=15 => "terrain/tree2.png",
-                16..=19 => "terrain/pine2.png",
-                20..=30 => "terrain/grass1.png",
-                _ => "terrain/grass2.png",
+                0 => "tree6",
+                1 => "pine6",
+                2..=3 => "tree3",
+                4..=5 => "pine3",
+                6..=8 => "tree2",
+                9..=11 => "pine2",
+                12..=15 => "tree2",
+                16..=19 => "pine2",
+                20..=30 => "grass1",
+                31..=45 => "grass2",
+                _ => "dry_grass",
             };
 
             // TODO Don't spawn MapSpace entities directly, but rather request for their creation.
-            commands
-                .spawn((MapSpace {
-                    col: col,
-                    row: row,
-                    texture_handle: asset_server.get_handle(tile_type),
-                },))
-                .with(MapSpaceRefreshNeeded);
+            // A typo in the name would panic right here, rather than
+            // turning up later as a missing texture.
+            commands.spawn((MapSpace::new(
+                col,
+                row,
+                tile_registry.handle(tile_type).unwrap(),
+            ),));
+        }
+    }
+
+    // The map's top left corner is at 0,0 in the world, so point the
+    // camera at the middle of what we've just made.
+    let centre =
+        (mapengine_map.grid_to_world(0, 0) + mapengine_map.grid_to_world(COLS - 1, ROWS - 1)) / 2.0;
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = centre.x;
+        transform.translation.y = centre.y;
+    }
+}
+
+/// This shows the mouse events: it prints which space the mouse is
+/// over, and turns spaces into dry grass when they're left-clicked.
+/// EventReaders are kept in Local resources, so each system has its
+/// own idea of which events it has already seen.
+fn mouse_demo_system(
+    mut hovered_reader: Local<EventReader<MapSpaceHovered>>,
+    hovered_events: Res<Events<MapSpaceHovered>>,
+    mut clicked_reader: Local<EventReader<MapSpaceClicked>>,
+    clicked_events: Res<Events<MapSpaceClicked>>,
+    tile_registry: Res<TileRegistry>,
+    mut mapspaces: Query<&mut MapSpace>,
+) {
+    for hovered in hovered_reader.iter(&hovered_events) {
+        println!("Mouse is over space {},{}", hovered.col, hovered.row);
+    }
+    for clicked in clicked_reader.iter(&clicked_events) {
+        if clicked.button != MouseButton::Left {
+            continue;
+        }
+        // Changing the MapSpace is all it takes for it to be redrawn.
+        if let (Ok(mut mapspace), Ok(dry_grass)) = (
+            mapspaces.get_mut(clicked.entity),
+            tile_registry.handle("dry_grass"),
+        ) {
+            *mapspace = MapSpace::new(clicked.col, clicked.row, dry_grass);
         }
     }
 }
@@ -105,8 +184,14 @@ fn main() {
         // This is a built-in-to-Bevy handy keyboard exit function
         .add_system(exit_on_esc_system.system())
         // This resource gives the configuration for the MapEngine plugin.
-        // In specific, it tells the folder to load the terrain tiles from.
-        .add_resource(MapEngineConfig::new("terrain"))
+        // In specific, it tells the folder to load the terrain tiles from,
+        // and the manifest which gives those tiles names and properties.
+        // With hot reload, editing a tile image updates the map right away.
+        .add_resource(
+            MapEngineConfig::new("terrain")
+                .with_manifest("terrain.tileset")
+                .with_hot_reload(),
+        )
         // And this is the MapEngine plugin — it loads all the systems
         // which handle putting entities with the MapSpace component
         // onto the actual map.
@@ -118,7 +203,11 @@ fn main() {
         // plugin will do this.
         .add_startup_system(setup_camera_system.system())
         // This inserts MapSpace entities from which the map will be built.
-        .add_startup_system(setup_demo_map_system.system())
+        // It's a regular system, not a startup one, because it has to wait
+        // for the tiles to be loaded.
+        .add_system(setup_demo_map_system.system())
+        // And this one reacts to the mouse.
+        .add_system(mouse_demo_system.system())
         // And finally, this, which fires off the actual game loop.
         .run()
 }
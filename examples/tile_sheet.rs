@@ -0,0 +1,69 @@
+/// This example shows how to use a tile sheet — one big image with
+/// lots of tiles on a grid — instead of a folder of separate images.
+///
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+
+// Until we have our own keyboard handling, this is handy...
+use bevy::input::system::exit_on_esc_system;
+
+// This is ... the thing being demonstrated here :)
+use bevy_mapengine::{MapEngineConfig, MapEnginePlugin, MapSpace, TileSheet};
+
+/*----------------------------------------------------------------------------*/
+
+/// The sheet that ships in the assets folder is 53 tiles across and
+/// 20 down, each 64×64 pixels.
+fn medieval_sheet() -> TileSheet {
+    TileSheet::new("medieval_tilesheet.png", 64, 64, 53, 20)
+}
+
+/// Set up a camera so we can see the world. The top left corner of the
+/// map is at 0,0, so we move it to the middle of the 20×10 spaces we fill in.
+fn setup_camera_system(commands: &mut Commands) {
+    let mut camera = Camera2dBundle::default();
+    camera.transform.translation.x = 20.0 * 64.0 / 2.0;
+    camera.transform.translation.y = -10.0 * 64.0 / 2.0;
+    commands.spawn(camera);
+}
+
+/// Lay out the first few rows of the sheet as map spaces, one tile per space.
+/// Tiles from a sheet are found by the sheet path plus "#" and the tile's index.
+fn setup_sheet_map_system(commands: &mut Commands, asset_server: Res<AssetServer>) {
+    let sheet = medieval_sheet();
+    for row in 0..10 {
+        for col in 0..20 {
+            let index = row * sheet.columns + col;
+            commands.spawn((MapSpace::new(
+                col as i32,
+                row as i32,
+                asset_server.get_handle(sheet.tile_path(index).as_str()),
+            ),));
+        }
+    }
+}
+
+/*----------------------------------------------------------------------------*/
+
+fn main() {
+    App::build()
+        .add_resource(WindowDescriptor {
+            title: "Bevy MapEngine Tile Sheet Example".to_string(),
+            width: 1280.,
+            height: 720.,
+            vsync: true,
+            resizable: false,
+            ..Default::default()
+        })
+        .add_plugins(DefaultPlugins)
+        .add_system(exit_on_esc_system.system())
+        // No tile folder this time, just the sheet.
+        .add_resource(MapEngineConfig::default().with_tile_sheet(medieval_sheet()))
+        .add_plugin(MapEnginePlugin)
+        .add_startup_system(setup_camera_system.system())
+        .add_startup_system(setup_sheet_map_system.system())
+        .run()
+}
@@ -0,0 +1,259 @@
+/// This module holds the ways a tile can be combined with what's
+/// already on the map texture, and the pixel-pushing code which
+/// actually does it.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+
+/*----------------------------------------------------------------------------*/
+
+/// How a tile's pixels are combined with what's underneath them.
+/// All of these respect the tile's alpha channel except Replace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Just copy the tile's pixels, transparency and all
+    Replace,
+    /// Normal "source-over" compositing: the tile is drawn on top,
+    /// and what's underneath shows through its transparent parts
+    AlphaOver,
+    /// Multiply the colour underneath by the tile's colour (for shadows,
+    /// tinting, and the like)
+    Multiply,
+    /// Add the tile's colour to what's underneath (for glows, highlights)
+    Additive,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::AlphaOver
+    }
+}
+
+/*----------------------------------------------------------------------------*/
+
+/// Fill a rectangle of the target texture with a single pixel value.
+/// Like everything here, this works on the RGBA bytes in the texture's
+/// data Vec, and assumes the Rgba8UnormSrgb map format.
+pub fn fill_rect(
+    target_texture: &mut Texture,
+    rect_x: usize,
+    rect_y: usize,
+    rect_width: usize,
+    rect_height: usize,
+    pixel: &[u8; 4],
+) {
+    let target_width = target_texture.size.width as usize;
+    for y in rect_y..rect_y + rect_height {
+        let begin = (y * target_width + rect_x) * 4;
+        let end = begin + rect_width * 4;
+        for target_pixel in target_texture.data[begin..end].chunks_exact_mut(4) {
+            target_pixel.copy_from_slice(pixel);
+        }
+    }
+}
+
+/// Draw the source texture onto the target texture with its top left
+/// at source_x, source_y, combining pixels according to the blend mode.
+///
+/// Only the part inside the clip rectangle (x, y, width, height) is
+/// drawn. That's how a tile bigger than its space spills over into the
+/// spaces around it one space at a time. The source can start off the
+/// top or left of the target, which is why its position is signed.
+///
+/// The maths is done on the sRGB-encoded values, as most 2D art tools
+/// do, with straight (not premultiplied) alpha.
+pub fn blend_texture(
+    target_texture: &mut Texture,
+    source_texture: &Texture,
+    source_x: i64,
+    source_y: i64,
+    clip: (usize, usize, usize, usize),
+    mode: BlendMode,
+) {
+    let source_width = source_texture.size.width as i64;
+    let source_height = source_texture.size.height as i64;
+    let target_width = target_texture.size.width as i64;
+    let target_height = target_texture.size.height as i64;
+    let (clip_x, clip_y, clip_width, clip_height) = clip;
+
+    // The rectangle we actually draw is where the source, the clip
+    // rectangle, and the target itself all overlap.
+    let left = source_x.max(clip_x as i64).max(0);
+    let top = source_y.max(clip_y as i64).max(0);
+    let right = (source_x + source_width)
+        .min((clip_x + clip_width) as i64)
+        .min(target_width);
+    let bottom = (source_y + source_height)
+        .min((clip_y + clip_height) as i64)
+        .min(target_height);
+    if left >= right || top >= bottom {
+        return;
+    }
+    let row_bytes = ((right - left) * 4) as usize;
+
+    for y in top..bottom {
+        let begin = ((y * target_width + left) * 4) as usize;
+        let texture_begin = (((y - source_y) * source_width + (left - source_x)) * 4) as usize;
+        let target_row = &mut target_texture.data[begin..begin + row_bytes];
+        let source_row = &source_texture.data[texture_begin..texture_begin + row_bytes];
+        if mode == BlendMode::Replace {
+            target_row.copy_from_slice(source_row);
+            continue;
+        }
+        for (target_pixel, source_pixel) in target_row
+            .chunks_exact_mut(4)
+            .zip(source_row.chunks_exact(4))
+        {
+            blend_pixel(target_pixel, source_pixel, mode);
+        }
+    }
+}
+
+/// Combine one source pixel into one target pixel
+fn blend_pixel(target: &mut [u8], source: &[u8], mode: BlendMode) {
+    // Fully transparent source pixels never change anything
+    // (and this is the common case for the edges of trees and such)
+    if source[3] == 0 {
+        return;
+    }
+    let source_alpha = source[3] as f32 / 255.0;
+    let target_alpha = target[3] as f32 / 255.0;
+
+    match mode {
+        BlendMode::Replace => target.copy_from_slice(source),
+        BlendMode::AlphaOver => {
+            let out_alpha = source_alpha + target_alpha * (1.0 - source_alpha);
+            for_each_colour(target, source, |t, s| {
+                (s * source_alpha + t * target_alpha * (1.0 - source_alpha)) / out_alpha
+            });
+            target[3] = to_byte(out_alpha);
+        }
+        BlendMode::Multiply => {
+            // Fade from the original towards the product by the source alpha
+            for_each_colour(target, source, |t, s| t + (t * s - t) * source_alpha);
+        }
+        BlendMode::Additive => {
+            for_each_colour(target, source, |t, s| t + s * source_alpha);
+            target[3] = to_byte(target_alpha + source_alpha);
+        }
+    }
+}
+
+/// Apply the function to each of the red, green, and blue components
+/// (as 0.0 to 1.0, target first) and store the result in the target.
+fn for_each_colour<F: Fn(f32, f32) -> f32>(target: &mut [u8], source: &[u8], f: F) {
+    for (t, &s) in target[..3].iter_mut().zip(source[..3].iter()) {
+        *t = to_byte(f(*t as f32 / 255.0, s as f32 / 255.0));
+    }
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+/*----------------------------------------------------------------------------*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::texture::{Extent3d, TextureDimension, TextureFormat};
+
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    fn solid(width: u32, height: u32, pixel: [u8; 4]) -> Texture {
+        Texture::new(
+            Extent3d::new(width, height, 1),
+            TextureDimension::D2,
+            pixel.repeat((width * height) as usize),
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    fn pixel_at(texture: &Texture, x: usize, y: usize) -> [u8; 4] {
+        let begin = (y * texture.size.width as usize + x) * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&texture.data[begin..begin + 4]);
+        pixel
+    }
+
+    /// Which pixels of a 64×32 target (two by two 32×16 spaces) are red
+    fn red_rows(texture: &Texture) -> Vec<String> {
+        (0..32)
+            .step_by(8)
+            .map(|y| {
+                (0..64)
+                    .step_by(8)
+                    .map(|x| {
+                        if pixel_at(texture, x, y) == RED {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tile_fills_exactly_its_non_square_space() {
+        let mut target = solid(64, 32, CLEAR);
+        let tile = solid(32, 16, RED);
+        // Space (1,1) of a 32×16 grid
+        blend_texture(
+            &mut target,
+            &tile,
+            32,
+            16,
+            (32, 16, 32, 16),
+            BlendMode::AlphaOver,
+        );
+        assert_eq!(
+            red_rows(&target),
+            vec!["........", "........", "....####", "....####"]
+        );
+        assert_eq!(pixel_at(&target, 32, 16), RED);
+        assert_eq!(pixel_at(&target, 63, 31), RED);
+        assert_eq!(pixel_at(&target, 31, 31), CLEAR);
+        assert_eq!(pixel_at(&target, 63, 15), CLEAR);
+    }
+
+    #[test]
+    fn tall_tile_is_clipped_to_one_space_at_a_time() {
+        let mut target = solid(64, 32, CLEAR);
+        let tree = solid(32, 32, RED);
+        // Standing on space (1,1), so reaching up into (1,0)
+        let (offset_x, offset_y) =
+            crate::map_space::TileAnchor::BottomCentre.offset((32, 32), (32, 16));
+        assert_eq!((offset_x, offset_y), (0, -16));
+        // First, just its own space
+        blend_texture(
+            &mut target,
+            &tree,
+            32 + offset_x,
+            16 + offset_y,
+            (32, 16, 32, 16),
+            BlendMode::Replace,
+        );
+        assert_eq!(
+            red_rows(&target),
+            vec!["........", "........", "....####", "....####"]
+        );
+        // Then the space above, when that one's drawn
+        blend_texture(
+            &mut target,
+            &tree,
+            32 + offset_x,
+            16 + offset_y,
+            (32, 0, 32, 16),
+            BlendMode::Replace,
+        );
+        assert_eq!(
+            red_rows(&target),
+            vec!["....####", "....####", "....####", "....####"]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+/// This module holds the errors the MapEngine can run into, and the
+/// resource and event which are used to tell the plugin user about them.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+// For reporting tiles in pixel formats we can't handle
+use bevy::render::texture::TextureFormat;
+
+// Standard rust things...
+use std::fmt;
+
+/*----------------------------------------------------------------------------*/
+
+/// Something that went wrong. These are sent as Bevy events (so listen
+/// with an `EventReader<MapEngineError>`), and the ones that have happened
+/// since the last load attempt are also kept in the MapEngineErrors resource.
+///
+/// Errors during loading or verifying put the engine into the
+/// MapEngineState::Error state. Errors while running (like a missing
+/// texture) are reported, but the engine keeps going.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapEngineError {
+    /// The tile folder couldn't be read
+    MissingFolder { folder: String, reason: String },
+    /// One or more tile assets (images, sheets, or the manifest) failed to load
+    LoadFailed { paths: Vec<String> },
+    /// No tiles at all were found
+    NoTiles,
+    /// A tile or tile sheet isn't the size it needs to be
+    SizeMismatch {
+        path: String,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    /// A tile or tile sheet texture has a depth other than 1
+    NotTwoDimensional { path: String },
+    /// A tile is in a pixel format which can't be converted to the map's
+    UnsupportedFormat { path: String, format: TextureFormat },
+    /// The tileset manifest doesn't match the loaded tiles
+    Manifest { problem: String },
+    /// A MapSpace's texture wasn't available when it was time to draw it
+    MissingTexture { entity: Entity, col: i32, row: i32 },
+    /// A tile was asked for by a name the TileRegistry doesn't know
+    UnknownTile { name: String },
+    /// A tile was registered with a name that's already taken
+    DuplicateTile { name: String },
+    /// Something was tried which needs the engine to be Running first
+    NotRunning,
+    /// A MapSpace asked for a map layer that isn't in MapEngineConfig
+    UnknownLayer { entity: Entity, name: String },
+    /// A MapSpace is outside the map's bounds, and the policy is Reject
+    OutOfBounds { entity: Entity, col: i32, row: i32 },
+}
+
+impl fmt::Display for MapEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapEngineError::MissingFolder { folder, reason } => {
+                write!(f, "problem loading tile folder {:?} ({})", folder, reason)
+            }
+            MapEngineError::LoadFailed { paths } => {
+                write!(f, "failed to load tile assets: {}", paths.join(", "))
+            }
+            MapEngineError::NoTiles => write!(f, "no tile textures were found"),
+            MapEngineError::SizeMismatch {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} is {:?}×{:?}, but should be {:?}×{:?}",
+                path, found.0, found.1, expected.0, expected.1
+            ),
+            MapEngineError::NotTwoDimensional { path } => {
+                write!(f, "{:?} isn't two-dimensional", path)
+            }
+            MapEngineError::UnsupportedFormat { path, format } => {
+                write!(f, "{:?} is in unsupported pixel format {:?}", path, format)
+            }
+            MapEngineError::Manifest { problem } => {
+                write!(f, "problem in tileset manifest: {}", problem)
+            }
+            MapEngineError::MissingTexture { entity, col, row } => write!(
+                f,
+                "texture for space {:?},{:?} ({:?}) is missing",
+                col, row, entity
+            ),
+            MapEngineError::UnknownTile { name } => write!(f, "there is no tile named {:?}", name),
+            MapEngineError::DuplicateTile { name } => {
+                write!(f, "there is already a tile named {:?}", name)
+            }
+            MapEngineError::NotRunning => write!(f, "the map engine isn't running yet"),
+            MapEngineError::UnknownLayer { entity, name } => {
+                write!(
+                    f,
+                    "space {:?} is on map layer {:?}, which doesn't exist",
+                    entity, name
+                )
+            }
+            MapEngineError::OutOfBounds { entity, col, row } => write!(
+                f,
+                "space {:?} at ({}, {}) is outside the map's bounds",
+                entity, col, row
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapEngineError {}
+
+/*----------------------------------------------------------------------------*/
+
+/// This global resource holds the errors that have happened since the
+/// last time loading was (re)started. An in-game error screen can show
+/// these while in the Error state.
+#[derive(Default)]
+pub struct MapEngineErrors {
+    pub errors: Vec<MapEngineError>,
+}
+
+/// Send this event while in the Error state to start loading the tiles
+/// all over again — for example, after fixing the broken image files.
+///
+/// The engine lets go of the tile assets when it enters the Error state,
+/// so Bevy throws them away and the retry reads every file from disk
+/// again. That only works if nothing else is holding on to one, though:
+/// if game code keeps a strong `Handle<Texture>` to a tile (from
+/// `asset_server.load()`, say), Bevy keeps that tile as it was, and the
+/// retry gets the old version back. Use `asset_server.get_handle()` for
+/// weak handles instead, or drop them before retrying.
+pub struct MapEngineRetryLoad;
+
+/// Report an error: print it, send it as an event, and keep it in
+/// the MapEngineErrors resource.
+pub(crate) fn report_error(
+    error: MapEngineError,
+    errors: &mut MapEngineErrors,
+    error_events: &mut Events<MapEngineError>,
+) {
+    eprintln!("Error! {}.", error);
+    error_events.send(error.clone());
+    errors.errors.push(error);
+}
+
+/// Report an error which stops loading, and switch to the Error state.
+pub(crate) fn enter_error_state(
+    error: MapEngineError,
+    state: &mut State<crate::MapEngineState>,
+    errors: &mut MapEngineErrors,
+    error_events: &mut Events<MapEngineError>,
+) {
+    report_error(error, errors, error_events);
+    // This only fails if we're already headed there, which is fine.
+    let _ = state.set_next(crate::MapEngineState::Error);
+}
+
+/// This system runs while in the Error state, and waits for a
+/// MapEngineRetryLoad event. When it gets one, it clears out the old
+/// errors and goes back to the Loading state to try again.
+pub fn retry_load_system(
+    mut retry_reader: Local<EventReader<MapEngineRetryLoad>>,
+    retry_events: Res<Events<MapEngineRetryLoad>>,
+    mut state: ResMut<State<crate::MapEngineState>>,
+    mut errors: ResMut<MapEngineErrors>,
+) {
+    if retry_reader.iter(&retry_events).next().is_some() {
+        println!("Retrying tile load...");
+        errors.errors.clear();
+        state.set_next(crate::MapEngineState::Loading).unwrap();
+    }
+}
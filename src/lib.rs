@@ -15,15 +15,82 @@
 // This is the basic Bevy game engine stuff
 use bevy::prelude::*;
 
-pub use map_space::{MapSpace, MapSpaceRefreshNeeded};
+pub use blend::BlendMode;
+pub use error::{MapEngineError, MapEngineErrors, MapEngineRetryLoad};
+pub use load_progress::{MapEngineLoadEvent, MapEngineLoadProgress, TileFileStatus};
+pub use map::{Map, MapBounds, MapLayer, OutOfBoundsPolicy};
+pub use map_space::{MapSpace, MapSpaceLayer, MapSpaceRefreshNeeded, TileAnchor};
+pub use overlap::{MapSpaceOverlap, OverlapPolicy};
+pub use picking::{
+    MapCursor, MapSpaceClicked, MapSpaceHovered, MapSpacePressed, MapSpaceReleased,
+    MapSpaceUnhovered,
+};
+pub use space_index::{MapSpaceIndex, SpacePosition};
+pub use tile_manifest::{TileInfo, TileProperty, Tileset};
+pub use tile_registry::TileRegistry;
+pub use tile_sheet::TileSheet;
 
 /*----------------------------------------------------------------------------*/
 
 /// This will eventually hold all parameters a user might want to configure.
-/// But for now, just the tile folder.
+/// But for now, just where to find the tiles: a folder of individual
+/// images, any number of tile sheets, or both — plus an optional
+/// manifest describing them.
 /// FUTURE use the builder pattern here
 pub struct MapEngineConfig {
+    /// Folder to load individual tile images from. May be empty if all
+    /// tiles come from sheets.
     pub tile_folder: String,
+    /// Sheets to cut into tiles, in addition to the folder.
+    pub tile_sheets: Vec<TileSheet>,
+    /// Path to a `.tileset` manifest naming the tiles. See Tileset.
+    pub manifest: Option<String>,
+    /// Watch tile images for changes, and redraw the map when they change.
+    pub hot_reload: bool,
+    /// Each space on the bottom map layer is cleared to this before its
+    /// tile is drawn, so it's what shows through transparent parts of
+    /// tiles (and where there's no tile). Defaults to transparent.
+    pub background: Color,
+    /// Names of the map layers, bottom first. Each gets its own texture
+    /// and sprite; see MapLayer. If none are given, there's just the one,
+    /// called "default".
+    pub map_layers: Vec<String>,
+    /// How many spaces across and down each chunk of the map is. Each
+    /// chunk is one texture, so this times the tile size must fit in
+    /// the GPU's largest texture size. Defaults to 32×32.
+    pub chunk_cols: u32,
+    pub chunk_rows: u32,
+    /// If given, the map is limited to these, and all of the chunks inside
+    /// them are made up front. Otherwise, it grows as spaces are added.
+    pub bounds: Option<MapBounds>,
+    /// What to do with spaces outside of the bounds. Defaults to Grow.
+    /// If the game calls Map::set_out_of_bounds first, that wins.
+    pub out_of_bounds: OutOfBoundsPolicy,
+    /// The size of a map space in pixels. If not given, it's the size of
+    /// the tiles, which must then all be the same.
+    pub space_size: Option<(u32, u32)>,
+    /// If given, check for MapSpaces which share a cell, and deal with
+    /// them this way. Off by default.
+    pub overlap_policy: Option<OverlapPolicy>,
+}
+
+impl Default for MapEngineConfig {
+    fn default() -> Self {
+        MapEngineConfig {
+            tile_folder: String::new(),
+            tile_sheets: Vec::new(),
+            manifest: None,
+            hot_reload: false,
+            background: Color::rgba(0.0, 0.0, 0.0, 0.0),
+            map_layers: Vec::new(),
+            chunk_cols: 32,
+            chunk_rows: 32,
+            bounds: None,
+            out_of_bounds: OutOfBoundsPolicy::default(),
+            space_size: None,
+            overlap_policy: None,
+        }
+    }
 }
 
 impl MapEngineConfig {
@@ -31,8 +98,95 @@ impl MapEngineConfig {
     pub fn new<S: Into<String>>(tile_folder: S) -> MapEngineConfig {
         MapEngineConfig {
             tile_folder: tile_folder.into(),
+            ..Default::default()
         }
     }
+
+    /// Add a tile sheet to be cut into tiles when loading.
+    /// Use with `MapEngineConfig::default()` for a sheet-only tileset.
+    pub fn with_tile_sheet(mut self, sheet: TileSheet) -> MapEngineConfig {
+        self.tile_sheets.push(sheet);
+        self
+    }
+
+    /// Load the given manifest along with the tiles
+    pub fn with_manifest<S: Into<String>>(mut self, manifest: S) -> MapEngineConfig {
+        self.manifest = Some(manifest.into());
+        self
+    }
+
+    /// Redraw the map live when tile images are changed on disk
+    pub fn with_hot_reload(mut self) -> MapEngineConfig {
+        self.hot_reload = true;
+        self
+    }
+
+    /// Set the colour which shows through transparent parts of tiles
+    pub fn with_background(mut self, background: Color) -> MapEngineConfig {
+        self.background = background;
+        self
+    }
+
+    /// Add a map layer on top of any already given. MapSpaces go on the
+    /// first (bottom) one unless they say otherwise.
+    pub fn with_map_layer<S: Into<String>>(mut self, name: S) -> MapEngineConfig {
+        self.map_layers.push(name.into());
+        self
+    }
+
+    /// Use chunks of a different size. Bigger chunks mean fewer sprites,
+    /// but more to send to the GPU whenever a space in one changes.
+    pub fn with_chunk_size(mut self, chunk_cols: u32, chunk_rows: u32) -> MapEngineConfig {
+        self.chunk_cols = chunk_cols.max(1);
+        self.chunk_rows = chunk_rows.max(1);
+        self
+    }
+
+    /// Limit the map to the given bounds; see Map::set_bounds
+    pub fn with_bounds(mut self, bounds: MapBounds) -> MapEngineConfig {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// Choose what happens to spaces outside of the bounds
+    pub fn with_out_of_bounds(mut self, policy: OutOfBoundsPolicy) -> MapEngineConfig {
+        self.out_of_bounds = policy;
+        self
+    }
+
+    /// Set the size of a map space, rather than taking it from the tiles.
+    /// Tiles can then be bigger than a space (but not smaller), and
+    /// overhang into the spaces around — see TileAnchor for how they line up.
+    pub fn with_space_size(mut self, width: u32, height: u32) -> MapEngineConfig {
+        self.space_size = Some((width, height));
+        self
+    }
+
+    /// Turn on the check for MapSpaces which share a cell; see OverlapPolicy
+    pub fn with_overlap_check(mut self, policy: OverlapPolicy) -> MapEngineConfig {
+        self.overlap_policy = Some(policy);
+        self
+    }
+
+    /// The map layers to make, bottom first — including the default one
+    /// if none were asked for.
+    pub(crate) fn map_layer_names(&self) -> Vec<String> {
+        if self.map_layers.is_empty() {
+            vec![map::DEFAULT_MAP_LAYER.to_string()]
+        } else {
+            self.map_layers.clone()
+        }
+    }
+
+    /// The background colour as a pixel in the map's format (8-bit sRGB)
+    pub(crate) fn background_pixel(&self) -> [u8; 4] {
+        [
+            (self.background.r() * 255.0).round() as u8,
+            (self.background.g() * 255.0).round() as u8,
+            (self.background.b() * 255.0).round() as u8,
+            (self.background.a() * 255.0).round() as u8,
+        ]
+    }
 }
 
 /*----------------------------------------------------------------------------*/
@@ -52,6 +206,36 @@ mod map_space;
 /// For the actual representation of the tile map
 mod map;
 
+/// Describing tile sheets and slicing them into tiles
+mod tile_sheet;
+
+/// The tileset manifest file, and the Tileset resource built from it
+mod tile_manifest;
+
+/// Errors, and how we report them
+mod error;
+
+/// Reporting on how loading is going
+mod load_progress;
+
+/// Looking up tiles by name or id
+mod tile_registry;
+
+/// Converting tiles to the map's pixel format
+mod tile_format;
+
+/// Blend modes, and drawing tiles onto the map with them
+mod blend;
+
+/// Keeping track of which MapSpace is where
+mod space_index;
+
+/// Checking for MapSpaces which share a cell
+mod overlap;
+
+/// Turning the mouse into events about MapSpaces
+mod picking;
+
 /*----------------------------------------------------------------------------*/
 
 /// Bevy groups systems into stages. Our mapengine
@@ -61,12 +245,14 @@ const MAPENGINE_STAGE: &str = "mapengine_stage";
 
 /// Bevy does "lazy" loading of assets. We switch from the
 /// Loading state to Running state when all of the tile images
-/// are actually loaded.
+/// are actually loaded. If anything goes wrong on the way,
+/// we go to the Error state instead; see MapEngineError.
 #[derive(Clone)]
 pub enum MapEngineState {
     Loading,
     Verifying,
     Running,
+    Error,
 }
 
 /*----------------------------------------------------------------------------*/
@@ -77,6 +263,21 @@ impl Plugin for MapEnginePlugin {
     fn build(&self, app: &mut AppBuilder) {
         // A stash of handles to our image tiles, so we can use them everywhere.
         app.init_resource::<tileloader_systems::MapEngineTileHandles>()
+            // The manifest is its own kind of asset, with a loader for .tileset files
+            .add_asset::<tile_manifest::TileManifest>()
+            .init_asset_loader::<tile_manifest::TileManifestLoader>()
+            // And this is what the manifest becomes once checked.
+            .init_resource::<tile_manifest::Tileset>()
+            // Every tile, by name and id, once they're all verified.
+            .init_resource::<tile_registry::TileRegistry>()
+            // Problems are sent as events, and also kept in a resource.
+            .add_event::<error::MapEngineError>()
+            .init_resource::<error::MapEngineErrors>()
+            // And this event asks to try loading again after an error.
+            .add_event::<error::MapEngineRetryLoad>()
+            // While loading, progress is kept in a resource and sent as events.
+            .init_resource::<load_progress::MapEngineLoadProgress>()
+            .add_event::<load_progress::MapEngineLoadEvent>()
             // This adds a "Stage" (basically, a group of systems) set up to handle our
             // various "States". Our stage, used in the MapEngine, will run right after
             // the default UPDATE stage. This is important because otherwise we will miss
@@ -90,8 +291,20 @@ impl Plugin for MapEnginePlugin {
             // This global resource tracks the state used in this stage.
             // We set it to Loading to start, of course.
             .add_resource(State::new(MapEngineState::Loading))
-            // And this global resource holds the texture for our map.
+            // And this global resource holds the textures for our map.
             .add_resource(map::Map::default())
+            // And this one remembers which MapSpace is where (see MapSpaceIndex).
+            .init_resource::<space_index::MapSpaceIndex>()
+            // Sent by the (optional) check for spaces which share a cell.
+            .add_event::<overlap::MapSpaceOverlap>()
+            // The mouse, in map terms: a resource for where the cursor is,
+            // and events for hovering over and clicking on spaces.
+            .init_resource::<picking::MapCursor>()
+            .add_event::<picking::MapSpaceHovered>()
+            .add_event::<picking::MapSpaceUnhovered>()
+            .add_event::<picking::MapSpacePressed>()
+            .add_event::<picking::MapSpaceReleased>()
+            .add_event::<picking::MapSpaceClicked>()
             // This stage happens once when entering the Loading state (that is, right away)
             .on_state_enter(
                 MAPENGINE_STAGE,
@@ -107,19 +320,44 @@ impl Plugin for MapEnginePlugin {
             )
             // This stage makes sure that our tiles are valid and stores information
             // about them in the global MapEngineMap resource, and then advances
-            // the state to Running. It exits on failure; we could get even more
-            // fancy and instead have an Error state which presents error messages in-game.
+            // the state to Running. On failure, it goes to the Error state instead.
             .on_state_enter(
                 MAPENGINE_STAGE,
                 MapEngineState::Verifying,
                 tileloader_systems::verify_tiles_system.system(),
             )
-            // When we get to the Running state, add our map sprite
+            // On an error, let go of the tiles, so a retry loads them afresh
+            .on_state_enter(
+                MAPENGINE_STAGE,
+                MapEngineState::Error,
+                tileloader_systems::release_tiles_system.system(),
+            )
+            // While in the Error state, we wait for a request to try again.
+            // (It's up to the plugin user to present the errors in-game.)
+            .on_state_update(
+                MAPENGINE_STAGE,
+                MapEngineState::Error,
+                error::retry_load_system.system(),
+            )
+            // When we get to the Running state, set up our map layers
             .on_state_enter(
                 MAPENGINE_STAGE,
                 MapEngineState::Running,
                 map_systems::create_map_sprite_system.system(),
             )
+            // This one also runs while Running, and watches for changed tile images.
+            .on_state_update(
+                MAPENGINE_STAGE,
+                MapEngineState::Running,
+                tileloader_systems::tile_reload_system.system(),
+            )
+            // And this one keeps the layers' sprites in step with the
+            // z, visibility, and opacity settings in the Map resource.
+            .on_state_update(
+                MAPENGINE_STAGE,
+                MapEngineState::Running,
+                map_systems::map_layer_settings_system.system(),
+            )
             // This system runs every frame once we are in the Running state.
             // Because it happens all the time, it needs to be careful to not
             // do slow things. See the code in the maptexture_update_system itself.
@@ -127,12 +365,21 @@ impl Plugin for MapEnginePlugin {
                 MAPENGINE_STAGE,
                 MapEngineState::Running,
                 map_systems::maptexture_update_system.system(),
+            )
+            // And this one checks for spaces which share a cell, if the
+            // config asks it to.
+            .on_state_update(
+                MAPENGINE_STAGE,
+                MapEngineState::Running,
+                overlap::overlap_check_system.system(),
+            )
+            // And finally, this one works out which space the mouse is over
+            // (after the others, so it sees the map as just drawn).
+            .on_state_update(
+                MAPENGINE_STAGE,
+                MapEngineState::Running,
+                picking::map_picking_system.system(),
             );
-        // FUTURE add a validator which runs periodically and checks for overlapping MapSpaces?
-        // NEXT add a system which takes mouse events and translates them into new events that
-        // correspond to the mapspace location (enter, exit, click -- maybe motion?)
-        // NEXT possibly also a global resource for current hovered or selected mapspace entity?
-        // FUTURE map scrolling (with the mouse stuff still working!)
-        // FUTURE map zooming (with the mouse stuff still working!)
+        // FUTURE map scrolling and zooming (picking already follows the camera)
     }
 }
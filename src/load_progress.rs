@@ -0,0 +1,66 @@
+/// This module holds the resource and events which tell the plugin user
+/// how loading the tiles is going — for a loading screen, for example.
+/*----------------------------------------------------------------------------*/
+//
+
+// Each file's status is just what the asset server tells us
+use bevy::asset::LoadState;
+
+/*----------------------------------------------------------------------------*/
+
+/// How one of the files we're waiting on is doing
+#[derive(Debug, Clone)]
+pub struct TileFileStatus {
+    /// Asset path of the file (a tile image, a tile sheet, or the manifest)
+    pub path: String,
+    pub state: LoadState,
+}
+
+/// This global resource is filled in when loading starts, and kept
+/// up to date during the Loading state. The counts cover every file
+/// being loaded: tile images, tile sheets, and the manifest.
+#[derive(Debug, Clone, Default)]
+pub struct MapEngineLoadProgress {
+    pub loaded: usize,
+    pub failed: usize,
+    pub total: usize,
+    pub files: Vec<TileFileStatus>,
+}
+
+impl MapEngineLoadProgress {
+    /// How far along we are, from 0.0 to 1.0 — handy for a progress bar.
+    /// Failed files count as done.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed) as f32 / self.total as f32
+        }
+    }
+
+    /// Paths of the files which failed to load
+    pub fn failed_paths(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .filter(|file| file.state == LoadState::Failed)
+            .map(|file| file.path.as_str())
+    }
+}
+
+/// Events sent during the Loading state. Listen for these with
+/// an `EventReader<MapEngineLoadEvent>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapEngineLoadEvent {
+    /// Loading has begun, with this many files to load
+    Started { total: usize },
+    /// Some file has finished loading (or failed to)
+    Progress {
+        loaded: usize,
+        failed: usize,
+        total: usize,
+    },
+    /// Everything loaded; next the tiles will be verified
+    Finished { total: usize },
+    /// Everything is done, but these files failed to load
+    Failed { paths: Vec<String> },
+}
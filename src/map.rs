@@ -1,7 +1,7 @@
-/// In our current implementation, the visible map is handled as
-/// one giant sprite. This module holds the struct which defines
-/// a Sprite to be MapEngineSprite, and a global resource which
-/// holds the configuration for all such sprites.
+/// In our current implementation, each layer of the visible map is
+/// split into chunks, each handled as one big sprite. This module holds
+/// the struct which defines a Sprite to be MapEngineSprite, and a global
+/// resource which holds the configuration for all such sprites.
 /*----------------------------------------------------------------------------*/
 //
 
@@ -9,45 +9,588 @@
 use bevy::prelude::*;
 
 // These are used for creating the map texture
+use bevy::asset::HandleId;
 use bevy::render::texture::{Extent3d, TextureDimension, TextureFormat};
 
+// Standard rust things...
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+
 /*----------------------------------------------------------------------------*/
 
-/// This component tags a sprite as map sprite
-pub struct MapEngineSprite;
+/// This component tags a sprite as map sprite. There's one for each
+/// chunk of each MapLayer, and this says which.
+pub struct MapEngineSprite {
+    /// As an index into `Map::layers()`
+    pub layer: usize,
+    /// As given by `Map::chunk_of`
+    pub chunk: (i32, i32),
+}
 
 /*----------------------------------------------------------------------------*/
 
-/// This is for the global resource that holds our map information.
-pub struct Map {
-    /// The actual texture to be drawn on
-    pub texture: Texture,
-    /// Width of map in spaces (texture width = cols × space_width_pixels)
+/// The name of the layer used when MapEngineConfig doesn't list any.
+pub const DEFAULT_MAP_LAYER: &str = "default";
+
+/// One of the map's named layers — "terrain", "objects", "fog", or
+/// whatever the game declared in MapEngineConfig. Each layer has its
+/// own textures, shown by its own sprites, so that hiding or fading one
+/// doesn't mean redrawing the others.
+///
+/// The name and chunks are managed by the engine, but z, visible,
+/// and opacity can be changed at any time through the Map resource,
+/// and the sprites will follow along on the next frame:
+///
+/// ```ignore
+/// if let Some(fog) = map.layer_mut("fog") {
+///     fog.opacity = 0.5;
+/// }
+/// ```
+pub struct MapLayer {
+    /// As given in MapEngineConfig
+    pub(crate) name: String,
+    /// The textures the layer is drawn on, one per chunk (see Map),
+    /// made as spaces are drawn in them. They live in `Assets<Texture>`,
+    /// like any other, and are changed in place there.
+    pub(crate) chunks: HashMap<(i32, i32), Handle<Texture>>,
+    /// Where the layer's sprites sit in the z order. Starts out as
+    /// the layer's position in the config: 0.0, 1.0, 2.0, and so on.
+    pub z: f32,
+    /// Whether the layer's sprites are drawn at all
+    pub visible: bool,
+    /// From 0.0 (invisible) to 1.0 (fully opaque); this is applied on
+    /// top of the transparency of the tiles themselves.
+    pub opacity: f32,
+}
+
+impl MapLayer {
+    /// A new, visible, fully opaque layer, with nothing drawn on it yet
+    pub(crate) fn new<S: Into<String>>(name: S, z: f32) -> MapLayer {
+        MapLayer {
+            name: name.into(),
+            chunks: HashMap::new(),
+            z,
+            visible: true,
+            opacity: 1.0,
+        }
+    }
+
+    /// The layer's name, as given in MapEngineConfig
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/*----------------------------------------------------------------------------*/
+
+/// The part of the map that spaces are allowed in, if it's limited.
+/// See MapEngineConfig::with_bounds and Map::set_bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapBounds {
+    /// The left-most column inside the bounds
+    pub first_col: i32,
+    /// The top-most row inside the bounds
+    pub first_row: i32,
+    /// How many columns across the bounds are
     pub cols: i32,
-    /// Height of map in spaces (texture height = rows × space_height_pixels)
+    /// How many rows down the bounds are
     pub rows: i32,
+}
+
+impl MapBounds {
+    /// Bounds of the given size, with (0,0) in the top left
+    pub fn new(cols: i32, rows: i32) -> MapBounds {
+        MapBounds {
+            first_col: 0,
+            first_row: 0,
+            cols: cols.max(1),
+            rows: rows.max(1),
+        }
+    }
+
+    /// Move the top left corner somewhere other than (0,0)
+    pub fn with_origin(mut self, first_col: i32, first_row: i32) -> MapBounds {
+        self.first_col = first_col;
+        self.first_row = first_row;
+        self
+    }
+
+    /// The right-most column inside the bounds
+    pub fn last_col(&self) -> i32 {
+        self.first_col + self.cols - 1
+    }
+
+    /// The bottom-most row inside the bounds
+    pub fn last_row(&self) -> i32 {
+        self.first_row + self.rows - 1
+    }
+
+    /// Is col, row inside the bounds?
+    pub fn contains(&self, col: i32, row: i32) -> bool {
+        col >= self.first_col
+            && col <= self.last_col()
+            && row >= self.first_row
+            && row <= self.last_row()
+    }
+
+    /// The nearest position to col, row which is inside the bounds
+    pub fn clamp(&self, col: i32, row: i32) -> (i32, i32) {
+        (
+            col.max(self.first_col).min(self.last_col()),
+            row.max(self.first_row).min(self.last_row()),
+        )
+    }
+
+    /// Grow the bounds (if need be) so that col, row is inside them
+    pub fn include(&mut self, col: i32, row: i32) {
+        let last_col = cmp::max(self.last_col(), col);
+        let last_row = cmp::max(self.last_row(), row);
+        self.first_col = cmp::min(self.first_col, col);
+        self.first_row = cmp::min(self.first_row, row);
+        self.cols = last_col - self.first_col + 1;
+        self.rows = last_row - self.first_row + 1;
+    }
+}
+
+/// What happens to a space which is outside the map's bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfBoundsPolicy {
+    /// Don't draw it, and send a MapEngineError::OutOfBounds event
+    Reject,
+    /// Draw it at the nearest position inside the bounds instead
+    Clamp,
+    /// Grow the bounds to take it in (and this is what happens when
+    /// there aren't any bounds, too)
+    Grow,
+}
+
+impl Default for OutOfBoundsPolicy {
+    fn default() -> Self {
+        OutOfBoundsPolicy::Grow
+    }
+}
+
+/*----------------------------------------------------------------------------*/
+
+/// This is for the global resource that holds our map information.
+///
+/// GPUs have a limit on how big a texture can be (often 8192 or 16384
+/// pixels across), so rather than one texture for the whole map, each
+/// layer is split into chunks of chunk_cols × chunk_rows spaces. Each
+/// chunk has its own texture and sprite, and only the chunks with
+/// changed spaces are redrawn.
+///
+/// In the world, the top left corner of space (0,0) is at 0,0, columns
+/// go to the right (+x), and rows go down (−y). `grid_to_world` and
+/// `world_to_grid` convert between the two.
+///
+/// Cols and rows can be negative, too; those spaces are just further
+/// left or up, in chunks with negative numbers. Nothing already drawn
+/// has to move when the map grows that way — the new chunks are simply
+/// put to the left of or above the old ones.
+///
+/// Or, the map can be given fixed bounds, either in MapEngineConfig or
+/// with set_bounds. Then all of the chunks inside them are made at once,
+/// and spaces outside them are dealt with according to out_of_bounds.
+///
+/// The fields are the engine's own bookkeeping, so they're private;
+/// game code gets at what it needs through the methods below.
+pub struct Map {
+    /// The layers, bottom first. These are made from MapEngineConfig
+    /// when the engine starts Running, so this is empty until then.
+    pub(crate) layers: Vec<MapLayer>,
+    /// The left-most column the map reaches. This starts at 0, and only
+    /// changes if a space is put at a negative col.
+    pub(crate) first_col: i32,
+    /// The top-most row the map reaches. This starts at 0, and only
+    /// changes if a space is put at a negative row.
+    pub(crate) first_row: i32,
+    /// Width of map in spaces, from first_col to the furthest-right col so far
+    pub(crate) cols: i32,
+    /// Height of map in spaces, from first_row to the furthest-down row so far
+    pub(crate) rows: i32,
+    /// Width of each chunk in spaces, from MapEngineConfig
+    pub(crate) chunk_cols: i32,
+    /// Height of each chunk in spaces, from MapEngineConfig
+    pub(crate) chunk_rows: i32,
     /// Each space must be the same; keeping it here saves us reading it later.
-    pub space_width_pixels: usize,
+    /// Spaces don't have to be square (32×16 is fine, for instance), so
+    /// always use this for x and space_height_pixels for y.
+    pub(crate) space_width_pixels: usize,
     /// Each space must be the same; keeping it here saves us reading it later.
-    pub space_height_pixels: usize,
+    pub(crate) space_height_pixels: usize,
+    /// If tiles may be bigger than a space (see MapEngineConfig::with_space_size)
+    pub(crate) overhang_allowed: bool,
+    /// The biggest tile width so far, for working out how far tiles overhang
+    pub(crate) max_tile_width: usize,
+    /// The biggest tile height so far, for working out how far tiles overhang
+    pub(crate) max_tile_height: usize,
+    /// Drawn in place of any tile whose texture isn't available
+    pub(crate) missing_tile: Handle<Texture>,
+    /// Tiles we've found missing (and so warned about) while drawing
+    pub(crate) missing_tiles: HashSet<HandleId>,
+    /// Textures in the wrong pixel format we've found (and warned about)
+    /// while drawing. Those are drawn as the missing tile placeholder.
+    pub(crate) wrong_format_tiles: HashSet<HandleId>,
+    /// The last problem reported for each space which can't be drawn
+    /// (on an unknown layer, or out of bounds), so that it's reported
+    /// once rather than every time the space changes
+    pub(crate) space_problems: HashMap<Entity, crate::error::MapEngineError>,
+    /// Where spaces are allowed, if that's limited. Use set_bounds to change this.
+    pub(crate) bounds: Option<MapBounds>,
+    /// What to do with spaces outside of the bounds, if the game has
+    /// said. If not, MapEngineConfig's choice is used when Running starts.
+    pub(crate) out_of_bounds: Option<OutOfBoundsPolicy>,
+    /// Set when the bounds change, so that the chunks inside them get made
+    pub(crate) allocate_bounds: bool,
 }
 
 impl Default for Map {
-    /// default to an empty texture
+    /// default to no layers at all
     fn default() -> Self {
         Map {
-            // We start with the minimum possible texture size: 1×1
-            // FUTURE have a reasonable default and make configurable
-            texture: Texture::new_fill(
-                Extent3d::new(1, 1, 1),
-                TextureDimension::D2,
-                &[0, 0, 0, 0],
-                TextureFormat::Rgba8UnormSrgb,
-            ),
+            layers: Vec::new(),
+            first_col: 0,
+            first_row: 0,
             cols: 0,
             rows: 0,
+            // These are filled in when we start Running
+            chunk_cols: 0,
+            chunk_rows: 0,
             space_width_pixels: 0,
             space_height_pixels: 0,
+            overhang_allowed: false,
+            max_tile_width: 0,
+            max_tile_height: 0,
+            // This is filled in when we start Running and know the size
+            missing_tile: Handle::default(),
+            missing_tiles: HashSet::new(),
+            wrong_format_tiles: HashSet::new(),
+            space_problems: HashMap::new(),
+            bounds: None,
+            out_of_bounds: None,
+            allocate_bounds: false,
+        }
+    }
+}
+
+impl Map {
+    /// All of the layers, bottom first. A layer's position in here is
+    /// its layer number, as used by MapSpaceIndex. This is empty until
+    /// the engine is Running.
+    pub fn layers(&self) -> &[MapLayer] {
+        &self.layers
+    }
+
+    /// Find a layer by name
+    pub fn layer(&self, name: &str) -> Option<&MapLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// Find a layer by name, to change its z, visibility, or opacity
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut MapLayer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    /// Which layer a MapSpace with the given `map_layer` goes on:
+    /// the named one, or the first (bottom) one for None. This is the
+    /// layer number that MapSpaceIndex lookups use.
+    pub fn layer_index(&self, name: Option<&str>) -> Option<usize> {
+        match name {
+            Some(name) => self.layers.iter().position(|layer| layer.name == name),
+            None if self.layers.is_empty() => None,
+            None => Some(0),
+        }
+    }
+
+    /// Limit the map to the given bounds (or, with None, take the limit
+    /// away). All of the chunks inside them are made on the next frame,
+    /// so that drawing doesn't have to do it bit by bit later. Anything
+    /// already drawn outside them stays where it is until it's changed.
+    pub fn set_bounds(&mut self, bounds: Option<MapBounds>) {
+        self.bounds = bounds;
+        self.allocate_bounds = bounds.is_some();
+    }
+
+    /// The bounds, if the map has any
+    pub fn bounds(&self) -> Option<MapBounds> {
+        self.bounds
+    }
+
+    /// What happens to spaces outside the bounds
+    pub fn out_of_bounds(&self) -> OutOfBoundsPolicy {
+        self.out_of_bounds.unwrap_or_default()
+    }
+
+    /// Change what happens to spaces outside the bounds. This only
+    /// affects spaces placed or moved from now on. Setting it before
+    /// the engine is Running takes the place of MapEngineConfig's choice.
+    pub fn set_out_of_bounds(&mut self, policy: OutOfBoundsPolicy) {
+        self.out_of_bounds = Some(policy);
+    }
+
+    /// The part of the map which has had spaces in it so far, from
+    /// (first_col, first_row) — which can be negative — across cols and
+    /// down rows. Cols and rows are 0 until the first space is drawn.
+    pub fn extent(&self) -> (i32, i32, i32, i32) {
+        (self.first_col, self.first_row, self.cols, self.rows)
+    }
+
+    /// The size of one space, in pixels: width, then height. This is
+    /// 0, 0 until the engine is Running.
+    pub fn space_size(&self) -> (usize, usize) {
+        (self.space_width_pixels, self.space_height_pixels)
+    }
+
+    /// How many spaces across and down each chunk is
+    pub fn chunk_size(&self) -> (i32, i32) {
+        (self.chunk_cols, self.chunk_rows)
+    }
+
+    /// Where a space at col, row should actually be drawn, according to
+    /// the bounds and the out-of-bounds policy — or None if it shouldn't
+    /// be drawn at all. With the Grow policy, this grows the bounds.
+    pub(crate) fn place_in_bounds(&mut self, col: i32, row: i32) -> Option<(i32, i32)> {
+        let mut bounds = match self.bounds {
+            Some(bounds) if !bounds.contains(col, row) => bounds,
+            _ => return Some((col, row)),
+        };
+        match self.out_of_bounds() {
+            OutOfBoundsPolicy::Reject => None,
+            OutOfBoundsPolicy::Clamp => Some(bounds.clamp(col, row)),
+            OutOfBoundsPolicy::Grow => {
+                bounds.include(col, row);
+                self.bounds = Some(bounds);
+                Some((col, row))
+            }
+        }
+    }
+
+    /// Grow the map's extent (first_col, first_row, cols, and rows) to
+    /// take in the space at col, row.
+    pub(crate) fn include(&mut self, col: i32, row: i32) {
+        // The -1 is because cols and rows are counts, so if everything
+        // is in col 0, there's 1 col and the last one is 0.
+        let last_col = cmp::max(self.first_col + self.cols - 1, col);
+        let last_row = cmp::max(self.first_row + self.rows - 1, row);
+        self.first_col = cmp::min(self.first_col, col);
+        self.first_row = cmp::min(self.first_row, row);
+        self.cols = last_col - self.first_col + 1;
+        self.rows = last_row - self.first_row + 1;
+    }
+
+    /// Which chunk the space at col, row is in. Chunk (0,0) has spaces
+    /// (0,0) to (chunk_cols − 1, chunk_rows − 1), chunk (1,0) is to its
+    /// right, and so on.
+    pub fn chunk_of(&self, col: i32, row: i32) -> (i32, i32) {
+        (
+            col.div_euclid(self.chunk_cols),
+            row.div_euclid(self.chunk_rows),
+        )
+    }
+
+    /// Set the size of a space: the configured size, if there is one, in
+    /// which case tiles may be bigger and overhang; or otherwise the size
+    /// of the first tile (width and height both), which every other tile
+    /// then has to match. Gives back the size chosen.
+    pub(crate) fn size_spaces(
+        &mut self,
+        configured: Option<(u32, u32)>,
+        first_tile: (u32, u32),
+    ) -> (u32, u32) {
+        let size = configured.unwrap_or(first_tile);
+        self.space_width_pixels = size.0 as usize;
+        self.space_height_pixels = size.1 as usize;
+        self.overhang_allowed = configured.is_some();
+        size
+    }
+
+    /// Is a tile of this size usable on this map? Normally, tiles have
+    /// to be exactly the size of a space. If overhang is allowed, they
+    /// can be bigger (but not smaller).
+    pub(crate) fn tile_fits(&self, width: u32, height: u32) -> bool {
+        let space = (
+            self.space_width_pixels as u32,
+            self.space_height_pixels as u32,
+        );
+        if self.overhang_allowed {
+            width >= space.0 && height >= space.1
+        } else {
+            (width, height) == space
+        }
+    }
+
+    /// Keep track of the biggest tile size, so we know how far tiles reach.
+    pub(crate) fn note_tile_size(&mut self, width: u32, height: u32) {
+        self.max_tile_width = cmp::max(self.max_tile_width, width as usize);
+        self.max_tile_height = cmp::max(self.max_tile_height, height as usize);
+    }
+
+    /// How many spaces across and down a tile can reach past its own
+    /// space, in any direction (because it depends on the anchor).
+    /// This is 0, 0 when all of the tiles are space-sized.
+    pub(crate) fn overhang_reach(&self) -> (i32, i32) {
+        let reach = |tile: usize, space: usize| {
+            if space == 0 {
+                0
+            } else {
+                ((tile.saturating_sub(space) + space - 1) / space) as i32
+            }
+        };
+        (
+            reach(self.max_tile_width, self.space_width_pixels),
+            reach(self.max_tile_height, self.space_height_pixels),
+        )
+    }
+
+    /// Has the given chunk of the given layer been made yet?
+    pub(crate) fn has_chunk(&self, layer_index: usize, chunk: (i32, i32)) -> bool {
+        self.layers[layer_index].chunks.contains_key(&chunk)
+    }
+
+    /// Where the top left of space col, row is within its chunk's texture, in pixels
+    pub(crate) fn pixel_in_chunk(&self, col: i32, row: i32) -> (usize, usize) {
+        (
+            col.rem_euclid(self.chunk_cols) as usize * self.space_width_pixels,
+            row.rem_euclid(self.chunk_rows) as usize * self.space_height_pixels,
+        )
+    }
+
+    /// The size of each chunk's texture, in pixels
+    pub(crate) fn chunk_size_pixels(&self) -> (u32, u32) {
+        (
+            (self.chunk_cols as usize * self.space_width_pixels) as u32,
+            (self.chunk_rows as usize * self.space_height_pixels) as u32,
+        )
+    }
+
+    /// The world position of the centre of the given chunk, which is
+    /// where its sprite goes
+    pub(crate) fn chunk_centre(&self, chunk: (i32, i32)) -> Vec2 {
+        let (width, height) = self.chunk_size_pixels();
+        Vec2::new(
+            chunk.0 as f32 * width as f32 + width as f32 / 2.0,
+            -(chunk.1 as f32 * height as f32 + height as f32 / 2.0),
+        )
+    }
+
+    /// The world position of the centre of the space at col, row
+    pub fn grid_to_world(&self, col: i32, row: i32) -> Vec2 {
+        let width = self.space_width_pixels as f32;
+        let height = self.space_height_pixels as f32;
+        Vec2::new(
+            col as f32 * width + width / 2.0,
+            -(row as f32 * height + height / 2.0),
+        )
+    }
+
+    /// Which space the given world position is in. (This works for
+    /// anywhere, whether or not there's a MapSpace there.)
+    pub fn world_to_grid(&self, position: Vec2) -> (i32, i32) {
+        (
+            (position.x / self.space_width_pixels as f32).floor() as i32,
+            (-position.y / self.space_height_pixels as f32).floor() as i32,
+        )
+    }
+}
+
+/// Make the placeholder texture for missing tiles: a magenta-and-black
+/// checkerboard, which is hard to mistake for anything on purpose.
+pub fn missing_tile_texture(width: usize, height: usize) -> Texture {
+    const SQUARE: usize = 8;
+    let mut data = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            if (x / SQUARE + y / SQUARE) % 2 == 0 {
+                data.extend_from_slice(&[255, 0, 255, 255]);
+            } else {
+                data.extend_from_slice(&[0, 0, 0, 255]);
+            }
         }
     }
+    Texture::new(
+        Extent3d::new(width as u32, height as u32, 1),
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/*----------------------------------------------------------------------------*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A map whose spaces are 32×16, as they'd be after loading 32×16
+    /// tiles, in chunks of 4×4 spaces
+    fn map_with_32x16_spaces() -> Map {
+        let mut map = Map::default();
+        map.size_spaces(None, (32, 16));
+        map.chunk_cols = 4;
+        map.chunk_rows = 4;
+        map
+    }
+
+    #[test]
+    fn space_height_comes_from_tile_height() {
+        let map = map_with_32x16_spaces();
+        assert_eq!(map.space_size(), (32, 16));
+        assert!(map.tile_fits(32, 16));
+        // Square tiles of either side don't fit, and neither does the
+        // same tile turned on its side.
+        assert!(!map.tile_fits(32, 32));
+        assert!(!map.tile_fits(16, 16));
+        assert!(!map.tile_fits(16, 32));
+    }
+
+    #[test]
+    fn configured_space_size_lets_tiles_overhang() {
+        let mut map = Map::default();
+        assert_eq!(map.size_spaces(Some((32, 16)), (32, 48)), (32, 16));
+        assert!(map.tile_fits(32, 16));
+        assert!(map.tile_fits(32, 48));
+        assert!(!map.tile_fits(32, 8));
+        map.note_tile_size(32, 48);
+        assert_eq!(map.overhang_reach(), (0, 2));
+    }
+
+    #[test]
+    fn grid_to_world_is_the_centre_of_a_non_square_space() {
+        let map = map_with_32x16_spaces();
+        assert_eq!(map.grid_to_world(0, 0), Vec2::new(16.0, -8.0));
+        assert_eq!(map.grid_to_world(1, 1), Vec2::new(48.0, -24.0));
+        assert_eq!(map.grid_to_world(-1, -1), Vec2::new(-16.0, 8.0));
+    }
+
+    #[test]
+    fn grid_and_world_round_trip() {
+        let map = map_with_32x16_spaces();
+        for row in -5..5 {
+            for col in -5..5 {
+                assert_eq!(map.world_to_grid(map.grid_to_world(col, row)), (col, row));
+            }
+        }
+        // Edges: the top left corner of a space is in it, and the bottom
+        // right corner is in the next one along.
+        assert_eq!(map.world_to_grid(Vec2::new(0.0, 0.0)), (0, 0));
+        assert_eq!(map.world_to_grid(Vec2::new(31.5, -15.5)), (0, 0));
+        assert_eq!(map.world_to_grid(Vec2::new(32.0, -16.0)), (1, 1));
+        assert_eq!(map.world_to_grid(Vec2::new(-0.5, 0.5)), (-1, -1));
+    }
+
+    #[test]
+    fn pixel_in_chunk_uses_width_for_x_and_height_for_y() {
+        let map = map_with_32x16_spaces();
+        assert_eq!(map.chunk_size_pixels(), (128, 64));
+        assert_eq!(map.pixel_in_chunk(0, 0), (0, 0));
+        assert_eq!(map.pixel_in_chunk(1, 1), (32, 16));
+        assert_eq!(map.pixel_in_chunk(3, 2), (96, 32));
+        // The next chunk along starts again from 0
+        assert_eq!(map.chunk_of(5, 2), (1, 0));
+        assert_eq!(map.pixel_in_chunk(5, 2), (32, 32));
+        // And negative spaces count back from the far side of their chunk
+        assert_eq!(map.chunk_of(-1, -1), (-1, -1));
+        assert_eq!(map.pixel_in_chunk(-1, -1), (96, 48));
+    }
 }
@@ -7,28 +7,163 @@
 use bevy::prelude::*;
 
 /// This is a Bevy Component that defines an Entity as representing
-/// a space on our map, and holds the location and the tile image
-/// to use. Note that these are meant to represent fixed locations
-/// on the map; x and y should not change. Note also that I haven't
-/// decided on how to do layering, so duplicating (col,row) will
-/// lead to unpredictable results.
+/// a space on our map, and holds the location and the tile images
+/// to use. A space can be moved by changing col and row (or its
+/// map_layer); the cell it leaves is redrawn without it.
 ///
-/// FUTURE consider making col and row read-only using the readonly crate
-/// FUTURE make texture_handle a Vec, and draw in order?
-/// The other layering approach (adding depth, allowing multiple col,row)
-/// has the disadvantage that we need to find all of the entities to draw.
-// TODO texture handle should not need to be public, so we need a constructor
+/// Usually there should be one space per (col,row) on each map layer,
+/// and layers used to stack tiles. But if several spaces do share a
+/// cell, they're all drawn, in the order they arrived there — so the
+/// one placed or moved there most recently is on top. Taking it away
+/// again uncovers the others.
+///
+/// A space can show several tiles stacked up in layers — ground,
+/// then decoration, then maybe a selection marker. The space is
+/// cleared to the background colour (see MapEngineConfig), and then
+/// each layer is drawn in order, first to last, using its blend mode.
+/// Changing any layer redraws the whole stack.
+///
+/// That's within one space. Separately, the whole map is split into
+/// named map layers (see MapLayer), each its own sprite, and a space
+/// is drawn on one of those — so the same (col,row) can have one
+/// space on "terrain" and another on "fog".
+///
+/// To get rid of a space, despawn its entity (or remove the MapSpace
+/// component) in the UPDATE stage or earlier. The map engine finds out
+/// about removals in its own stage, which comes after UPDATE, and Bevy
+/// forgets them at the end of each frame — so a space removed in a later
+/// stage is missed, and stays drawn on the map (and in the
+/// MapSpaceIndex) until something else redraws its cell.
 #[derive(Debug)]
 pub struct MapSpace {
-    /// Column (x) position of this tile on the map. 0 is on the left.
+    /// Column (x) position of this tile on the map. Bigger is further
+    /// right; negative numbers are fine, and go left of column 0.
     pub col: i32,
-    /// Row (y) position of this tile on the map. 0 is at the top.
+    /// Row (y) position of this tile on the map. Bigger is further
+    /// down; negative numbers are fine, and go above row 0.
     pub row: i32,
-    /// load with, for example, `asset_server.get_handle("terrain/grass1.png")`
+    /// The tiles to draw, bottom first
+    pub layers: Vec<MapSpaceLayer>,
+    /// Which of the map's named layers (see MapEngineConfig) this space
+    /// is drawn on. None means the first (bottom) one.
+    pub map_layer: Option<String>,
+}
+
+/// One tile in a MapSpace's stack
+#[derive(Debug, Clone)]
+pub struct MapSpaceLayer {
+    /// load with, for example, `tile_registry.handle("grass1")`
     pub texture_handle: Handle<Texture>,
+    /// How the tile is combined with the layers underneath
+    pub blend_mode: crate::blend::BlendMode,
+    /// Where the tile sits in the space, if it's bigger than the space
+    pub anchor: TileAnchor,
+}
+
+/// Which point of a tile lines up with the same point of its space.
+/// This only matters for tiles bigger than a space (see
+/// MapEngineConfig::with_space_size); the rest of the tile overhangs
+/// into the spaces around.
+///
+/// The default, BottomCentre, suits trees and buildings, which stand
+/// on their own space and reach up into the one above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileAnchor {
+    TopLeft,
+    TopCentre,
+    Centre,
+    BottomLeft,
+    BottomCentre,
+    BottomRight,
+}
+
+impl Default for TileAnchor {
+    fn default() -> Self {
+        TileAnchor::BottomCentre
+    }
 }
 
-/// This component signals that a MapSpace needs to be refreshed.
-/// This is a hack until https://github.com/bevyengine/bevy/pull/1471 is implemented.
-// TODO make not public?
+impl TileAnchor {
+    /// Where the top left of a tile of the given size goes, in pixels
+    /// from the top left of a space of the given size. This is zero or
+    /// negative, because tiles are never smaller than spaces.
+    pub fn offset(&self, tile_size: (usize, usize), space_size: (usize, usize)) -> (i64, i64) {
+        // How far across and down the anchor point is, in halves
+        let (halves_x, halves_y) = match self {
+            TileAnchor::TopLeft => (0, 0),
+            TileAnchor::TopCentre => (1, 0),
+            TileAnchor::Centre => (1, 1),
+            TileAnchor::BottomLeft => (0, 2),
+            TileAnchor::BottomCentre => (1, 2),
+            TileAnchor::BottomRight => (2, 2),
+        };
+        (
+            (space_size.0 as i64 - tile_size.0 as i64) * halves_x / 2,
+            (space_size.1 as i64 - tile_size.1 as i64) * halves_y / 2,
+        )
+    }
+}
+
+impl MapSpace {
+    /// A space at the given position showing the given tile,
+    /// drawn with the default blend mode (AlphaOver).
+    pub fn new(col: i32, row: i32, texture_handle: Handle<Texture>) -> MapSpace {
+        MapSpace {
+            col,
+            row,
+            layers: vec![MapSpaceLayer {
+                texture_handle,
+                blend_mode: crate::blend::BlendMode::default(),
+                anchor: TileAnchor::default(),
+            }],
+            map_layer: None,
+        }
+    }
+
+    /// Add another tile on top, with the default blend mode
+    pub fn with_layer(mut self, texture_handle: Handle<Texture>) -> MapSpace {
+        self.layers.push(MapSpaceLayer {
+            texture_handle,
+            blend_mode: crate::blend::BlendMode::default(),
+            anchor: TileAnchor::default(),
+        });
+        self
+    }
+
+    /// Use a different blend mode for the top layer so far
+    pub fn with_blend_mode(mut self, blend_mode: crate::blend::BlendMode) -> MapSpace {
+        if let Some(layer) = self.layers.last_mut() {
+            layer.blend_mode = blend_mode;
+        }
+        self
+    }
+
+    /// Use a different anchor for the top layer so far
+    pub fn with_anchor(mut self, anchor: TileAnchor) -> MapSpace {
+        if let Some(layer) = self.layers.last_mut() {
+            layer.anchor = anchor;
+        }
+        self
+    }
+
+    /// Put this space on the named map layer, rather than the bottom one
+    pub fn on_map_layer<S: Into<String>>(mut self, name: S) -> MapSpace {
+        self.map_layer = Some(name.into());
+        self
+    }
+
+    /// Does any layer of this space use the given tile?
+    pub fn uses(&self, texture_handle: &Handle<Texture>) -> bool {
+        self.layers
+            .iter()
+            .any(|layer| layer.texture_handle == *texture_handle)
+    }
+}
+
+/// This component asks for a MapSpace to be drawn again even though
+/// it hasn't changed — for instance, because the texture behind one
+/// of its handles has. It's removed once the space is redrawn.
+///
+/// You don't need this for new spaces, or when changing one: the
+/// engine notices spawned and changed MapSpace components by itself.
 pub struct MapSpaceRefreshNeeded;
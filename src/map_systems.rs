@@ -9,39 +9,15 @@ use bevy::prelude::*;
 use bevy::render::texture::{Extent3d, TextureDimension, TextureFormat};
 
 // Standard rust things...
-use std::cmp;
+use std::collections::{HashMap, HashSet};
 
-/// Ripped from bevy_sprite/src/texture_atlas_builder.rs.
-///
-/// This doesn't really copy actual GPU textures. It copies bits
-/// in a Vec representing RGBA data. This is not going way we want
-/// to do this always, but we are waiting on
-/// https://github.com/bevyengine/bevy/issues/1207#issuecomment-800602680
-/// for a real solution.
-fn copy_texture(
-    target_texture: &mut Texture,
-    source_texture: &Texture,
-    rect_x: usize,
-    rect_y: usize,
-) {
-    let rect_width = source_texture.size.width as usize;
-    let rect_height = source_texture.size.height as usize;
-    let target_width = target_texture.size.width as usize;
-    let format_size = target_texture.format.pixel_size();
-
-    for (texture_y, bound_y) in (rect_y..rect_y + rect_height).enumerate() {
-        let begin = (bound_y * target_width + rect_x) * format_size;
-        let end = begin + rect_width * format_size;
-        let texture_begin = texture_y * rect_width * format_size;
-        let texture_end = texture_begin + rect_width * format_size;
-        target_texture.data[begin..end]
-            .copy_from_slice(&source_texture.data[texture_begin..texture_end]);
-    }
-}
+use crate::space_index::SpacePosition;
 
 /*----------------------------------------------------------------------------*/
 
-/// Creates the Sprite that shows our assembled map.
+/// Sets up the map once the tiles are ready: the placeholder for missing
+/// tiles, and the map layers. The sprites which actually show the map are
+/// made chunk by chunk, as spaces are drawn; see maptexture_update_system.
 ///
 /// This system gets Commands, which is a queue which can be used to spawn or
 /// remove Elements from the World, which is basically the container for
@@ -52,143 +28,598 @@ fn copy_texture(
 pub fn create_map_sprite_system(
     commands: &mut Commands,
     mut textures: ResMut<Assets<Texture>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mapengine_map: Res<crate::map::Map>,
+    mut mapengine_map: ResMut<crate::map::Map>,
+    map_engine_config: Res<crate::MapEngineConfig>,
+    mapspaces: Query<Entity, With<crate::map_space::MapSpace>>,
 ) {
-    // The resource MapEngineMap should already be defined, including
-    // a tiny empty texture.
-    // This line does two things: adds that texture as a global resource,
-    // and also gets us a handle to put into the SpriteBundle as a material.
-    // Bevy needs both of these things in order to actually render.
-    let map_texture_handle = textures.add(mapengine_map.texture.clone());
-
-    // And here is our "sprite" which shows the whole map. I use "sprite"
-    // in scare quotes because it might be quite a bit larger than what
-    // that name normally implies, but, hey, we work with what we have.
-    // We add the MapEngineSprite component so we can keep this straight
-    // from any other sprites.
-    commands
-        .spawn(SpriteBundle {
-            material: materials.add(map_texture_handle.into()),
-            ..Default::default()
-        })
-        .with(crate::map::MapEngineSprite);
+    // Now that the tiles are verified we know how big a space is, so
+    // this is the time to make the placeholder for missing tiles.
+    mapengine_map.missing_tile = textures.add(crate::map::missing_tile_texture(
+        mapengine_map.space_width_pixels,
+        mapengine_map.space_height_pixels,
+    ));
+
+    // Make the layers named in the config, stacked up in z order in the
+    // order given, and note how big their chunks are.
+    mapengine_map.chunk_cols = map_engine_config.chunk_cols.max(1) as i32;
+    mapengine_map.chunk_rows = map_engine_config.chunk_rows.max(1) as i32;
+    if mapengine_map.layers.is_empty() {
+        mapengine_map.layers = map_engine_config
+            .map_layer_names()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| crate::map::MapLayer::new(name, i as f32))
+            .collect();
+    }
+
+    // And the bounds, if any, and what to do outside them. (Unless the
+    // game has already set those on the Map itself.)
+    if mapengine_map.out_of_bounds.is_none() {
+        mapengine_map.out_of_bounds = Some(map_engine_config.out_of_bounds);
+    }
+    if mapengine_map.bounds.is_none() {
+        mapengine_map.set_bounds(map_engine_config.bounds);
+    }
+
+    // Spaces spawned before we were Running were new back then, and
+    // Bevy only tells us about new and changed components for the frame
+    // they happen in. So, mark them all to be drawn now.
+    for entity in mapspaces.iter() {
+        commands.insert_one(entity, crate::map_space::MapSpaceRefreshNeeded);
+    }
 }
 
-/// Draw spaces that need updated onto the map texture.
+/// Keeps each layer's sprites in step with the z, visible, and opacity
+/// settings in the Map resource, so game code only has to change those.
 ///
-/// TODO Handle removal of spaces, not just addition
+/// This runs every frame, so it only touches a sprite (or its material)
+/// when something is actually different; otherwise Bevy would see a
+/// change every frame and do extra work on its side too.
+pub fn map_layer_settings_system(
+    mapengine_map: Res<crate::map::Map>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut mapsprites: Query<(
+        &crate::map::MapEngineSprite,
+        &mut Transform,
+        &mut Visible,
+        &Handle<ColorMaterial>,
+    )>,
+) {
+    for (sprite, mut transform, mut visible, material) in mapsprites.iter_mut() {
+        let layer = match mapengine_map.layers.get(sprite.layer) {
+            Some(layer) => layer,
+            None => continue,
+        };
+        if (transform.translation.z - layer.z).abs() > f32::EPSILON {
+            transform.translation.z = layer.z;
+        }
+        if visible.is_visible != layer.visible {
+            visible.is_visible = layer.visible;
+        }
+        let opacity = layer.opacity.max(0.0).min(1.0);
+        let current = materials.get(material).map(|material| material.color.a());
+        if matches!(current, Some(alpha) if (alpha - opacity).abs() > f32::EPSILON) {
+            materials.get_mut(material).unwrap().color.set_a(opacity);
+        }
+    }
+}
+
+/// Draw spaces that need updated onto the map's chunk textures, and clear
+/// away spaces that have been removed. Only the chunks with something to
+/// redraw are touched, and chunks (and their sprites) are made the first
+/// time something is drawn in them.
 ///
 /// This runs every frame when the engine is in the Running state, so it
 /// is important to not do slow things. Unfortunately, because Bevy
 /// does not yet support GPU texture-to-texture copy or batched rendering,
 /// there are more slow operations here than ideal.
 ///
-/// The first Query here returns MapSpace entities that have MapSpaceRefreshNeeded
-/// And the second one gets us all of our map sprites. (There might be
-/// more than one for a different view into the same map; that's to be implemented.)
+/// The chunk textures are changed in place, through their handles in
+/// `Assets<Texture>`. That tells Bevy to send the new pixels to the GPU,
+/// and the sprites' materials can go on using the same handles.
+///
+/// The first Query here returns MapSpace entities which are new or have
+/// changed since last frame, plus any marked with MapSpaceRefreshNeeded.
+/// The second gets all MapSpaces, for redrawing what's left at a cell when
+/// a space is removed from it (and for finding out about removals at all).
+#[allow(clippy::too_many_arguments)]
 pub fn maptexture_update_system(
     commands: &mut Commands,
     mut textures: ResMut<Assets<Texture>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut mapengine_map: ResMut<crate::map::Map>,
+    mut space_index: ResMut<crate::space_index::MapSpaceIndex>,
+    map_engine_config: Res<crate::MapEngineConfig>,
+    mut errors: ResMut<crate::error::MapEngineErrors>,
+    mut error_events: ResMut<Events<crate::error::MapEngineError>>,
     mapspaces: Query<
-        (Entity, &crate::map_space::MapSpace),
-        With<crate::map_space::MapSpaceRefreshNeeded>,
+        (
+            Entity,
+            &crate::map_space::MapSpace,
+            Option<&crate::map_space::MapSpaceRefreshNeeded>,
+        ),
+        Or<(
+            Changed<crate::map_space::MapSpace>,
+            With<crate::map_space::MapSpaceRefreshNeeded>,
+        )>,
     >,
-    mapsprites: Query<&Handle<ColorMaterial>, With<crate::map::MapEngineSprite>>,
+    all_mapspaces: Query<&crate::map_space::MapSpace>,
 ) {
-    // MapSpaces are entities in the World. They should be tagged
-    // with MapSpaceRefreshNeeded if they've changed in appearance,
-    // which will cause this system to get them.
-
-    // This first pass gathers information needed to size the map texture,
-    // and if we need to do anything at all.
-    // TODO This doubles the number of times we go through the list;
-    // consider if it is really the best way. (One idea for an alternate
-    // approach: check the map size when spawning a new mapspace, and
-    // mark it to grow if need be then.)
-    let mut count = 0;
-    for (_entity, mapspace) in mapspaces.iter() {
-        // Find the furthest-from 0,0 rows and columns.
-        // The +1 is because we are zero-indexed, so if everything is in col 0
-        // we still need a space_width-wide map.
-        mapengine_map.cols = cmp::max(mapengine_map.cols, mapspace.col + 1);
-        mapengine_map.rows = cmp::max(mapengine_map.rows, mapspace.row + 1);
-        count += 1;
-    }
-    // If there aren't any, exit now.
-    // TODO Refactor so this happens instantly at the beginning of the system
-    if count == 0 {
-        return;
+    // MapSpaces are entities in the World. Bevy keeps track of which
+    // components have been added or changed (that is, borrowed mutably)
+    // this frame, and the Changed filter gets us just those. Spaces can
+    // also be tagged with MapSpaceRefreshNeeded to be drawn again anyway.
+
+    // Bevy keeps a list of entities whose MapSpace component was removed
+    // this frame (including by despawning). Removed components are gone,
+    // so we look up where they were drawn in the space index.
+    let removed = all_mapspaces.removed::<crate::map_space::MapSpace>();
+
+    // We need to copy this out of the config, where it's stored as a Color.
+    let background = map_engine_config.background_pixel();
+
+    // If the map has been given bounds, make all of the chunks inside them
+    // now, all at once.
+    if mapengine_map.allocate_bounds {
+        mapengine_map.allocate_bounds = false;
+        if let Some(bounds) = mapengine_map.bounds {
+            let first_chunk = mapengine_map.chunk_of(bounds.first_col, bounds.first_row);
+            let last_chunk = mapengine_map.chunk_of(bounds.last_col(), bounds.last_row());
+            for layer_index in 0..mapengine_map.layers.len() {
+                for chunk_row in first_chunk.1..=last_chunk.1 {
+                    for chunk_col in first_chunk.0..=last_chunk.0 {
+                        let chunk = (chunk_col, chunk_row);
+                        if !mapengine_map.has_chunk(layer_index, chunk) {
+                            create_chunk(
+                                commands,
+                                &mut textures,
+                                &mut materials,
+                                &mut mapengine_map,
+                                layer_index,
+                                chunk,
+                                &layer_background(layer_index, background),
+                            );
+                        }
+                    }
+                }
+            }
+        }
     }
 
-    // We need to copy these out of the resource because later there's
-    // a mutable+immutable borrow attempt if we don't have our own copy.
-    let space_width_pixels = mapengine_map.space_width_pixels;
-    let space_height_pixels = mapengine_map.space_height_pixels;
-
-    // If our existing texture is too small, create a new bigger one.
-    if mapengine_map.texture.size.width < mapengine_map.cols as u32 * space_width_pixels as u32
-        || mapengine_map.texture.size.height
-            < mapengine_map.rows as u32 * space_height_pixels as u32
-    {
-        println!(
-            "Resizing map texture from {:?}×{:?} to {:?}×{:?}.",
-            mapengine_map.texture.size.width,
-            mapengine_map.texture.size.height,
-            mapengine_map.cols as u32 * space_width_pixels as u32,
-            mapengine_map.rows as u32 * space_height_pixels as u32,
-        );
-        let mut new_texture = Texture::new_fill(
-            Extent3d::new(
-                mapengine_map.cols as u32 * space_width_pixels as u32,
-                mapengine_map.rows as u32 * space_height_pixels as u32,
-                1,
-            ),
-            TextureDimension::D2,
-            // transparent
-            // FUTURE make this configurable
-            &[0, 0, 0, 0],
-            TextureFormat::Rgba8UnormSrgb,
-        );
+    // If there's nothing new, changed, or removed, exit now.
+    if mapspaces.iter().next().is_none() && removed.is_empty() {
+        return;
+    }
 
-        // copy the old texture to the new one — 0,0 for top left
-        copy_texture(&mut new_texture, &mapengine_map.texture, 0, 0);
+    // Rather than drawing spaces one by one, we work out which cells need
+    // redrawing, and then redraw each of those just once, with everything
+    // that's in it. That way moving a space off a cell it shared, or
+    // removing it, uncovers whatever was underneath.
+    let mut dirty_cells = HashSet::new();
 
-        // and swap it in.
-        mapengine_map.texture = new_texture;
+    // First, forget about removed spaces. If anything else is in the
+    // same cell on the same layer, it gets drawn again; otherwise,
+    // the cell is left empty, and the layers below show through.
+    for &entity in removed {
+        mapengine_map.space_problems.remove(&entity);
+        if let Some(position) = space_index.remove(entity) {
+            dirty_cells.insert(position);
+        }
     }
 
-    // And now we iterate through again and do the actual copying
-    for (entity, mapspace) in mapspaces.iter() {
-        // Each space has a handle to the texture which should represent it visually
-        match textures.get(&mapspace.texture_handle) {
-            Some(space_texture) => {
-                copy_texture(
-                    &mut mapengine_map.texture,
-                    &space_texture,
-                    mapspace.col as usize * space_width_pixels,
-                    mapspace.row as usize * space_height_pixels,
+    // Then, the new and changed ones. If a space has moved (including to
+    // a different map layer), the cell it left needs redrawing too.
+    for (entity, mapspace, refresh) in mapspaces.iter() {
+        if refresh.is_some() {
+            commands.remove_one::<crate::map_space::MapSpaceRefreshNeeded>(entity);
+        }
+
+        // Find the map layer this space is on. If it names one which
+        // doesn't exist, that's reported, and the space isn't drawn.
+        let layer_index = match mapengine_map.layer_index(mapspace.map_layer.as_deref()) {
+            Some(layer_index) => layer_index,
+            None => {
+                report_space_problem(
+                    entity,
+                    crate::error::MapEngineError::UnknownLayer {
+                        entity,
+                        name: mapspace.map_layer.clone().unwrap_or_default(),
+                    },
+                    &mut mapengine_map,
+                    &mut errors,
+                    &mut error_events,
                 );
+                if let Some(old_position) = space_index.remove(entity) {
+                    dirty_cells.insert(old_position);
+                }
+                continue;
             }
+        };
+
+        // Check the space against the map's bounds. It might be drawn
+        // somewhere else (Clamp), or not at all (Reject). Otherwise, this
+        // stretches the map's extent to take it in, in whichever direction
+        // (including up and left, to negative cols and rows).
+        let (col, row) = match mapengine_map.place_in_bounds(mapspace.col, mapspace.row) {
+            Some(cell) => cell,
             None => {
-                eprintln!("For some reason, a texture is missing.");
-                std::process::exit(2);
+                report_space_problem(
+                    entity,
+                    crate::error::MapEngineError::OutOfBounds {
+                        entity,
+                        col: mapspace.col,
+                        row: mapspace.row,
+                    },
+                    &mut mapengine_map,
+                    &mut errors,
+                    &mut error_events,
+                );
+                if let Some(old_position) = space_index.remove(entity) {
+                    dirty_cells.insert(old_position);
+                }
+                continue;
+            }
+        };
+        mapengine_map.include(col, row);
+        // Whatever was wrong with it before (if anything) isn't any more.
+        mapengine_map.space_problems.remove(&entity);
+        // A clamped space isn't really meant to be where it's drawn, so
+        // it shouldn't count against whatever is (see overlap_check_system).
+        let clamped = (col, row) != (mapspace.col, mapspace.row);
+
+        let position = (layer_index, col, row);
+        match space_index.position_of(entity) {
+            Some(old_position) if old_position == position => (),
+            old_position => {
+                if let Some(old_position) = old_position {
+                    dirty_cells.insert(old_position);
+                }
+                space_index.place(entity, position, !clamped);
+            }
+        }
+        dirty_cells.insert(position);
+    }
+
+    // A tile bigger than its space overhangs into the spaces around it,
+    // so when a space changes, so might its neighbours. (If all tiles are
+    // space-sized, the reach is 0 and this is just the cells themselves.)
+    // A cell in a chunk we've already made is redrawn either way, since
+    // there might be an old overhang to clear. But a cell in a chunk that
+    // doesn't exist yet has nothing on it to clear, so it's only worth
+    // drawing (and making the chunk for) if some space really does reach
+    // it now. Then, group the cells by the layer and chunk they're in,
+    // because it's chunk textures that we draw on.
+    let (reach_cols, reach_rows) = mapengine_map.overhang_reach();
+    let mut redraw_cells = HashSet::new();
+    for (layer_index, col, row) in dirty_cells {
+        for near_row in row - reach_rows..=row + reach_rows {
+            for near_col in col - reach_cols..=col + reach_cols {
+                let position = (layer_index, near_col, near_row);
+                if redraw_cells.contains(&position) {
+                    continue;
+                }
+                let chunk = mapengine_map.chunk_of(near_col, near_row);
+                if mapengine_map.has_chunk(layer_index, chunk)
+                    || cell_is_reached(
+                        &mapengine_map,
+                        &space_index,
+                        &all_mapspaces,
+                        &textures,
+                        position,
+                    )
+                {
+                    redraw_cells.insert(position);
+                }
             }
+        }
+    }
+    let mut dirty_chunks: HashMap<(usize, (i32, i32)), Vec<SpacePosition>> = HashMap::new();
+    for position in redraw_cells {
+        let (layer_index, col, row) = position;
+        dirty_chunks
+            .entry((layer_index, mapengine_map.chunk_of(col, row)))
+            .or_default()
+            .push(position);
+    }
+
+    // And now we do the actual copying, a chunk at a time. Each cell is
+    // cleared to the background, and then every space which reaches it is
+    // drawn, clipped to the cell. They're drawn a row at a time, top to
+    // bottom, so that something further down the map is in front of
+    // what's behind it (a tree's trunk covers the top of the tree below,
+    // and so on). Within one cell, they go in the order they arrived
+    // there, so the most recent ends up on top.
+    let mut lost = Vec::new();
+    for ((layer_index, chunk), positions) in dirty_chunks {
+        // (A chunk which doesn't exist yet only got here because something
+        // is going to be drawn on it; see above.)
+        let chunk_handle = match mapengine_map.layers[layer_index].chunks.get(&chunk) {
+            Some(chunk_handle) => chunk_handle.clone(),
+            None => create_chunk(
+                commands,
+                &mut textures,
+                &mut materials,
+                &mut mapengine_map,
+                layer_index,
+                chunk,
+                &layer_background(layer_index, background),
+            ),
         };
-        commands.remove_one::<crate::map_space::MapSpaceRefreshNeeded>(entity);
+
+        // We need to read tile textures from Assets<Texture> while drawing
+        // on the chunk texture, which is in there too, and Rust won't let
+        // us borrow it both ways at once. So, we take the chunk texture out
+        // (leaving an empty default one in its place), draw on it, and put
+        // it back. Getting it with get_mut tells Bevy it's been changed.
+        let mut chunk_texture = match textures.get_mut(&chunk_handle) {
+            Some(chunk_texture) => std::mem::take(chunk_texture),
+            None => continue,
+        };
+        for (_layer, col, row) in positions {
+            let pixel_origin = mapengine_map.pixel_in_chunk(col, row);
+            clear_space(
+                &mut chunk_texture,
+                &mapengine_map,
+                pixel_origin,
+                &layer_background(layer_index, background),
+            );
+            for near_row in row - reach_rows..=row + reach_rows {
+                for near_col in col - reach_cols..=col + reach_cols {
+                    // Where the top left of the nearby space would be in
+                    // this chunk's texture. (It may well be outside it.)
+                    let space_origin = (
+                        pixel_origin.0 as i64
+                            + (near_col - col) as i64 * mapengine_map.space_width_pixels as i64,
+                        pixel_origin.1 as i64
+                            + (near_row - row) as i64 * mapengine_map.space_height_pixels as i64,
+                    );
+                    for &entity in space_index.all_at(layer_index, near_col, near_row) {
+                        match all_mapspaces.get(entity) {
+                            Ok(mapspace) => draw_space(
+                                &mut chunk_texture,
+                                pixel_origin,
+                                space_origin,
+                                &mut mapengine_map,
+                                entity,
+                                mapspace,
+                                &textures,
+                                &mut errors,
+                                &mut error_events,
+                            ),
+                            // Removed after this system ran, so we never
+                            // heard about it (see MapSpace). Don't draw it,
+                            // and take it out of the index once we're done.
+                            Err(_) => lost.push(entity),
+                        }
+                    }
+                }
+            }
+        }
+        *textures.get_mut(&chunk_handle).unwrap() = chunk_texture;
     }
+    for entity in lost {
+        space_index.remove(entity);
+    }
+}
 
-    // As above, this does two things: gets us the handle to put into the
-    // sprite, and also adds the texture as a global resource. Bevy
-    // needs both of these things to happen in order to actually render.
-    let map_texture_handle = textures.add(mapengine_map.texture.clone());
+/// Does any space draw on the cell at the given position? That's any
+/// space in the cell itself, or one close enough with a tile big enough
+/// (and anchored the right way) to overhang into it.
+fn cell_is_reached(
+    map: &crate::map::Map,
+    space_index: &crate::space_index::MapSpaceIndex,
+    all_mapspaces: &Query<&crate::map_space::MapSpace>,
+    textures: &Assets<Texture>,
+    (layer_index, col, row): SpacePosition,
+) -> bool {
+    let (reach_cols, reach_rows) = map.overhang_reach();
+    let space_size = (map.space_width_pixels, map.space_height_pixels);
+    let (width, height) = (space_size.0 as i64, space_size.1 as i64);
+    space_index
+        .in_rect(
+            layer_index,
+            col - reach_cols,
+            row - reach_rows,
+            2 * reach_cols + 1,
+            2 * reach_rows + 1,
+        )
+        .into_iter()
+        .any(|(space_col, space_row, entity)| {
+            let mapspace = match all_mapspaces.get(entity) {
+                Ok(mapspace) => mapspace,
+                Err(_) => return false,
+            };
+            // Where the cell is, in pixels from the space's top left
+            let cell_x = (col - space_col) as i64 * width;
+            let cell_y = (row - space_row) as i64 * height;
+            mapspace.layers.iter().any(|layer| {
+                // A missing texture is drawn as the placeholder, which
+                // is exactly the size of a space.
+                let tile_size =
+                    textures
+                        .get(&layer.texture_handle)
+                        .map_or(space_size, |tile_texture| {
+                            (
+                                tile_texture.size.width as usize,
+                                tile_texture.size.height as usize,
+                            )
+                        });
+                let (tile_x, tile_y) = layer.anchor.offset(tile_size, space_size);
+                tile_x < cell_x + width
+                    && tile_x + tile_size.0 as i64 > cell_x
+                    && tile_y < cell_y + height
+                    && tile_y + tile_size.1 as i64 > cell_y
+            })
+        })
+}
 
-    // We only need to grab the first map sprite, because they
-    // all share the same material. And if there isn't one, that's fine;
-    // we'll update it once there is in a future pass.
-    if let Some(material) = mapsprites.iter().next() {
-        materials.get_mut(material).unwrap().texture = Some(map_texture_handle);
-    };
+/// Report a problem which stops a space being drawn, unless it's the same
+/// problem as was reported for that space last time. Otherwise, a space
+/// which is changed every frame (being animated, say) would fill up
+/// MapEngineErrors with the same error over and over.
+fn report_space_problem(
+    entity: Entity,
+    error: crate::error::MapEngineError,
+    map: &mut crate::map::Map,
+    errors: &mut crate::error::MapEngineErrors,
+    error_events: &mut Events<crate::error::MapEngineError>,
+) {
+    if map.space_problems.get(&entity) != Some(&error) {
+        map.space_problems.insert(entity, error.clone());
+        crate::error::report_error(error, errors, error_events);
+    }
+}
+
+/// Make the texture and sprite for a chunk of a layer, with nothing
+/// drawn on it yet, and give back the handle to the texture.
+fn create_chunk(
+    commands: &mut Commands,
+    textures: &mut Assets<Texture>,
+    materials: &mut Assets<ColorMaterial>,
+    map: &mut crate::map::Map,
+    layer_index: usize,
+    chunk: (i32, i32),
+    background: &[u8; 4],
+) -> Handle<Texture> {
+    let (width, height) = map.chunk_size_pixels();
+    // This does two things: adds the texture as a global resource, and
+    // also gets us a handle to put into the SpriteBundle as a material.
+    // Bevy needs both of these things in order to actually render. From
+    // here on, we draw on the texture in place, through that handle.
+    let chunk_handle = textures.add(Texture::new_fill(
+        Extent3d::new(width, height, 1),
+        TextureDimension::D2,
+        background,
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    // And here is our "sprite" which shows the chunk. I use "sprite"
+    // in scare quotes because it might be quite a bit larger than what
+    // that name normally implies, but, hey, we work with what we have.
+    // We add the MapEngineSprite component so we can keep this straight
+    // from any other sprites, and know which layer and chunk it shows.
+    // The material's colour is white (so the texture is unchanged) with
+    // the layer's opacity as alpha.
+    let centre = map.chunk_centre(chunk);
+    let layer = &mut map.layers[layer_index];
+    commands
+        .spawn(SpriteBundle {
+            material: materials.add(ColorMaterial {
+                color: Color::rgba(1.0, 1.0, 1.0, layer.opacity),
+                texture: Some(chunk_handle.clone()),
+            }),
+            transform: Transform::from_translation(centre.extend(layer.z)),
+            visible: Visible {
+                is_visible: layer.visible,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .with(crate::map::MapEngineSprite {
+            layer: layer_index,
+            chunk,
+        });
+    layer.chunks.insert(chunk, chunk_handle.clone());
+    chunk_handle
+}
+
+/// Only the bottom map layer is cleared to the background colour. The
+/// others are cleared to transparent, so that the layers below show
+/// through wherever they have nothing drawn.
+fn layer_background(layer_index: usize, background: [u8; 4]) -> [u8; 4] {
+    if layer_index == 0 {
+        background
+    } else {
+        [0, 0, 0, 0]
+    }
+}
+
+/// Fill the cell with its top left at pixel_origin in the given chunk
+/// texture with a single pixel value
+fn clear_space(
+    chunk_texture: &mut Texture,
+    map: &crate::map::Map,
+    pixel_origin: (usize, usize),
+    pixel: &[u8; 4],
+) {
+    crate::blend::fill_rect(
+        chunk_texture,
+        pixel_origin.0,
+        pixel_origin.1,
+        map.space_width_pixels,
+        map.space_height_pixels,
+        pixel,
+    );
+}
+
+/// Draw each of a space's tiles, in order, onto the given chunk texture,
+/// over whatever is there already. The space's top left is at
+/// space_origin, and only the cell with its top left at clip_origin is
+/// drawn on. Those are the same unless the tiles overhang their space.
+#[allow(clippy::too_many_arguments)]
+fn draw_space(
+    chunk_texture: &mut Texture,
+    clip_origin: (usize, usize),
+    space_origin: (i64, i64),
+    map: &mut crate::map::Map,
+    entity: Entity,
+    mapspace: &crate::map_space::MapSpace,
+    textures: &Assets<Texture>,
+    errors: &mut crate::error::MapEngineErrors,
+    error_events: &mut Events<crate::error::MapEngineError>,
+) {
+    for layer in mapspace.layers.iter() {
+        // Each layer has a handle to the texture which should represent it visually
+        let tile_texture = match textures.get(&layer.texture_handle) {
+            // Loaded and registered tiles are always converted to the map's
+            // pixel format, but a texture made some other way and put
+            // straight into a MapSpace might not be. Blending assumes the
+            // format matches, so draw the placeholder instead of that, and
+            // warn about it (again, just once per texture).
+            Some(tile_texture) if tile_texture.format != crate::tile_format::MAP_FORMAT => {
+                if map.wrong_format_tiles.insert(layer.texture_handle.id) {
+                    let warning = crate::error::MapEngineError::UnsupportedFormat {
+                        path: format!("{:?}", layer.texture_handle.id),
+                        format: tile_texture.format,
+                    };
+                    eprintln!("Warning: {}; drawing a placeholder.", warning);
+                    error_events.send(warning.clone());
+                    errors.errors.push(warning);
+                }
+                textures.get(&map.missing_tile).unwrap()
+            }
+            Some(tile_texture) => tile_texture,
+            None => {
+                // For some reason, a texture is missing. Warn about it (just
+                // once per texture), and draw the placeholder instead. If
+                // the texture turns up later, tile_reload_system notices and
+                // has the space redrawn.
+                if map.missing_tiles.insert(layer.texture_handle.id) {
+                    let warning = crate::error::MapEngineError::MissingTexture {
+                        entity,
+                        col: mapspace.col,
+                        row: mapspace.row,
+                    };
+                    eprintln!("Warning: {}; drawing a placeholder.", warning);
+                    error_events.send(warning.clone());
+                    errors.errors.push(warning);
+                }
+                textures.get(&map.missing_tile).unwrap()
+            }
+        };
+        let space_size = (map.space_width_pixels, map.space_height_pixels);
+        let tile_size = (
+            tile_texture.size.width as usize,
+            tile_texture.size.height as usize,
+        );
+        let (offset_x, offset_y) = layer.anchor.offset(tile_size, space_size);
+        crate::blend::blend_texture(
+            chunk_texture,
+            tile_texture,
+            space_origin.0 + offset_x,
+            space_origin.1 + offset_y,
+            (clip_origin.0, clip_origin.1, space_size.0, space_size.1),
+            layer.blend_mode,
+        );
+    }
 }
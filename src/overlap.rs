@@ -0,0 +1,131 @@
+/// This module holds the optional check for MapSpaces which share a
+/// cell, and what to do about them.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+
+/*----------------------------------------------------------------------------*/
+
+/// What the overlap check does when it finds more than one MapSpace
+/// at the same (col,row) on the same map layer. Whichever it is, a
+/// MapSpaceOverlap event is sent as well.
+///
+/// Several spaces in one cell is allowed, and drawn in a defined way
+/// (see MapSpace), but it's often a mistake — spawning a map twice,
+/// say, or forgetting to despawn the old space when replacing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Print a warning
+    Warn,
+    /// Panic in debug builds, so the mistake can't be missed, and
+    /// print a warning in release builds
+    PanicInDebug,
+    /// Despawn all but the space which arrived in the cell last.
+    ///
+    /// "Older" is about when each arrived in the cell, not when it was
+    /// spawned: moving an existing space onto one that was just spawned
+    /// despawns the new one.
+    DespawnOlder,
+}
+
+/// Sent when two or more MapSpaces end up in the same cell of the
+/// same map layer (if the check is turned on; see
+/// MapEngineConfig::with_overlap_check).
+///
+/// With OverlapPolicy::DespawnOlder, this is sent after all but the
+/// last of `entities` have been queued for despawning, so by the time
+/// it's read, those will be gone.
+#[derive(Debug, Clone)]
+pub struct MapSpaceOverlap {
+    /// The spaces, in the order they arrived in the cell, so the one
+    /// drawn on top is last
+    pub entities: Vec<Entity>,
+    /// The name of the map layer
+    pub layer: String,
+    pub col: i32,
+    pub row: i32,
+}
+
+/*----------------------------------------------------------------------------*/
+
+/// Runs every frame while Running, and looks at the cells which have
+/// gained a second (or third...) MapSpace since last time. The space
+/// index notes those as spaces are placed, so this doesn't have to
+/// look through the whole map.
+///
+/// If MapEngineConfig doesn't ask for the check, this does nothing
+/// (except forget about the crowded cells, so they don't pile up).
+///
+/// A space which was clamped into the bounds (OutOfBoundsPolicy::Clamp)
+/// doesn't set this off by landing on an edge space, because it isn't
+/// really meant to be there — otherwise one stray space could get the
+/// rightful edge space despawned.
+pub fn overlap_check_system(
+    commands: &mut Commands,
+    map_engine_config: Res<crate::MapEngineConfig>,
+    mapengine_map: Res<crate::map::Map>,
+    mut space_index: ResMut<crate::space_index::MapSpaceIndex>,
+    mut overlap_events: ResMut<Events<MapSpaceOverlap>>,
+) {
+    let crowded = space_index.take_crowded();
+    let policy = match map_engine_config.overlap_policy {
+        Some(policy) => policy,
+        None => return,
+    };
+
+    for position in crowded {
+        let (layer_index, col, row) = position;
+        // Something may have moved away again since
+        let entities = space_index.all_at(layer_index, col, row).to_vec();
+        if entities.len() < 2 {
+            continue;
+        }
+        let overlap = MapSpaceOverlap {
+            entities,
+            layer: mapengine_map
+                .layers
+                .get(layer_index)
+                .map(|layer| layer.name.clone())
+                .unwrap_or_default(),
+            col,
+            row,
+        };
+
+        match policy {
+            OverlapPolicy::Warn => {
+                eprintln!("Warning: overlapping map spaces: {:?}.", overlap);
+            }
+            OverlapPolicy::PanicInDebug => {
+                if cfg!(debug_assertions) {
+                    panic!("Overlapping map spaces: {:?}.", overlap);
+                }
+                eprintln!("Warning: overlapping map spaces: {:?}.", overlap);
+            }
+            OverlapPolicy::DespawnOlder => {
+                // The last one arrived most recently, so it stays. The
+                // rest are despawned, but we have to take them out of the
+                // space index ourselves: this runs after
+                // maptexture_update_system, and Bevy forgets about removed
+                // components at the end of the frame, so that system would
+                // never hear about these. Then, the one that's left is
+                // marked to be drawn again on the next frame, which redraws
+                // the cell without the others.
+                let (&newest, older) = overlap.entities.split_last().unwrap();
+                for &entity in older {
+                    space_index.remove(entity);
+                    commands.despawn(entity);
+                }
+                commands.insert_one(newest, crate::map_space::MapSpaceRefreshNeeded);
+                println!(
+                    "Despawned {} overlapping map space(s) at ({}, {}).",
+                    older.len(),
+                    col,
+                    row
+                );
+            }
+        }
+        overlap_events.send(overlap);
+    }
+}
@@ -0,0 +1,571 @@
+/// This module turns the mouse into MapSpace events: which space the
+/// cursor is over, and which ones get clicked.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+// Mouse buttons going up and down
+use bevy::input::mouse::MouseButtonInput;
+use bevy::input::ElementState;
+// For finding the 2D camera, so we know where on the map the cursor is
+use bevy::render::camera::Camera;
+use bevy::render::render_graph::base::camera::CAMERA_2D;
+use bevy::window::{CursorLeft, WindowId};
+
+// Standard rust things...
+use std::collections::HashMap;
+
+/*----------------------------------------------------------------------------*/
+
+/// Sent when the cursor moves onto a MapSpace. (That can happen without
+/// the mouse moving, too — if the map scrolls, or a space is moved or
+/// spawned under the cursor.)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapSpaceHovered {
+    pub entity: Entity,
+    pub col: i32,
+    pub row: i32,
+}
+
+/// Sent when the cursor leaves a MapSpace it was over. If the space was
+/// despawned, the entity won't exist any more by the time this arrives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapSpaceUnhovered {
+    pub entity: Entity,
+    pub col: i32,
+    pub row: i32,
+}
+
+/// Sent when a mouse button goes down over a MapSpace
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapSpacePressed {
+    pub entity: Entity,
+    pub col: i32,
+    pub row: i32,
+    pub button: MouseButton,
+}
+
+/// Sent when a mouse button comes up over a MapSpace (whether or not
+/// it went down over the same one)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapSpaceReleased {
+    pub entity: Entity,
+    pub col: i32,
+    pub row: i32,
+    pub button: MouseButton,
+}
+
+/// Sent when a mouse button goes down and comes back up over the same
+/// MapSpace. This comes after the MapSpaceReleased event for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapSpaceClicked {
+    pub entity: Entity,
+    pub col: i32,
+    pub row: i32,
+    pub button: MouseButton,
+}
+
+/// This global resource says where the mouse cursor is on the map, for
+/// systems which would rather look than listen for events. It's updated
+/// every frame while the MapEngine is Running.
+#[derive(Debug, Default)]
+pub struct MapCursor {
+    /// Where the cursor is in world coordinates, or None if it isn't
+    /// over the window (or there's no 2D camera)
+    pub position: Option<Vec2>,
+    /// Which map cell that is, whether or not there's a space there
+    pub cell: Option<(i32, i32)>,
+    /// The space the cursor is over, if any
+    pub hovered: Option<Entity>,
+}
+
+/// What the picking system remembers from frame to frame
+#[derive(Default)]
+pub struct MapPickingState {
+    cursor_reader: EventReader<CursorMoved>,
+    cursor_left_reader: EventReader<CursorLeft>,
+    button_reader: EventReader<MouseButtonInput>,
+    /// The last cursor position, in window coordinates, and which
+    /// window it was in (or None once it's left the window)
+    cursor: Option<(WindowId, Vec2)>,
+    /// The space each held-down button went down over
+    pressed: HashMap<MouseButton, Entity>,
+}
+
+/*----------------------------------------------------------------------------*/
+
+/// Runs every frame while Running, after the map is drawn, and works
+/// out which MapSpace is under the cursor.
+///
+/// Bevy gives us the cursor in window coordinates: pixels from the
+/// bottom left of the window. To get from that to the map, we take the
+/// middle of the window as the origin, and then apply the 2D camera's
+/// transform — so this keeps working when the camera is moved (to
+/// scroll the map) or scaled (to zoom it). That gives a world position,
+/// which Map::world_to_grid turns into a (col,row), and the
+/// MapSpaceIndex turns into an entity.
+///
+/// Where map layers are stacked, the space on the highest visible
+/// layer wins.
+///
+/// When the cursor leaves the window, whatever it was over is
+/// unhovered, and any buttons held down are forgotten, so letting go
+/// outside the window doesn't click anything.
+///
+/// Bevy keeps each kind of event separately, so we can't tell whether
+/// a button went down before or after the cursor moved in the same
+/// frame. All of a frame's button events are taken to happen where the
+/// cursor ended up. That only matters if a press, a move to another
+/// space, and a release all fit in one frame, which at any reasonable
+/// frame rate is faster than a person can click.
+///
+/// Because this only uses Bevy's CursorMoved, CursorLeft, and
+/// MouseButtonInput events, sending those by hand works as well as a
+/// real mouse.
+#[allow(clippy::too_many_arguments)]
+pub fn map_picking_system(
+    mut picking: Local<MapPickingState>,
+    cursor_events: Res<Events<CursorMoved>>,
+    cursor_left_events: Res<Events<CursorLeft>>,
+    button_events: Res<Events<MouseButtonInput>>,
+    windows: Res<Windows>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mapengine_map: Res<crate::map::Map>,
+    space_index: Res<crate::space_index::MapSpaceIndex>,
+    mut map_cursor: ResMut<MapCursor>,
+    mut hovered_events: ResMut<Events<MapSpaceHovered>>,
+    mut unhovered_events: ResMut<Events<MapSpaceUnhovered>>,
+    mut pressed_events: ResMut<Events<MapSpacePressed>>,
+    mut released_events: ResMut<Events<MapSpaceReleased>>,
+    mut clicked_events: ResMut<Events<MapSpaceClicked>>,
+) {
+    // Bevy can give us several cursor moves in a frame; the last one
+    // is where the cursor is now.
+    let cursor_moved = picking
+        .cursor_reader
+        .iter(&cursor_events)
+        .last()
+        .map(|cursor_moved| (cursor_moved.id, cursor_moved.position));
+    if cursor_moved.is_some() {
+        picking.cursor = cursor_moved;
+    }
+    // If it's left the window it was in, it isn't over anything. (If it
+    // came back in the same frame, the next move will say where.)
+    let left: Vec<WindowId> = picking
+        .cursor_left_reader
+        .iter(&cursor_left_events)
+        .map(|cursor_left| cursor_left.id)
+        .collect();
+    if let Some((window_id, _cursor)) = picking.cursor {
+        if left.contains(&window_id) {
+            picking.cursor = None;
+            picking.pressed.clear();
+        }
+    }
+
+    // We do all of this every frame, not just when the cursor moves,
+    // because the map can move under a cursor that stays still.
+    let position = picking
+        .cursor
+        .and_then(|(window_id, cursor)| cursor_to_world(window_id, cursor, &windows, &cameras));
+    let cell = position.map(|position| mapengine_map.world_to_grid(position));
+    let hovered = cell.and_then(|(col, row)| space_at(col, row, &mapengine_map, &space_index));
+
+    if hovered != map_cursor.hovered {
+        if let (Some(entity), Some((col, row))) = (map_cursor.hovered, map_cursor.cell) {
+            unhovered_events.send(MapSpaceUnhovered { entity, col, row });
+        }
+        if let (Some(entity), Some((col, row))) = (hovered, cell) {
+            hovered_events.send(MapSpaceHovered { entity, col, row });
+        }
+    }
+    map_cursor.position = position;
+    map_cursor.cell = cell;
+    map_cursor.hovered = hovered;
+
+    // Now the buttons. These go to whatever the cursor is over now.
+    let button_inputs: Vec<MouseButtonInput> = picking
+        .button_reader
+        .iter(&button_events)
+        .cloned()
+        .collect();
+    for button_input in button_inputs {
+        let button = button_input.button;
+        match button_input.state {
+            ElementState::Pressed => {
+                picking.pressed.remove(&button);
+                if let (Some(entity), Some((col, row))) = (hovered, cell) {
+                    picking.pressed.insert(button, entity);
+                    pressed_events.send(MapSpacePressed {
+                        entity,
+                        col,
+                        row,
+                        button,
+                    });
+                }
+            }
+            ElementState::Released => {
+                let pressed_on = picking.pressed.remove(&button);
+                if let (Some(entity), Some((col, row))) = (hovered, cell) {
+                    released_events.send(MapSpaceReleased {
+                        entity,
+                        col,
+                        row,
+                        button,
+                    });
+                    if pressed_on == Some(entity) {
+                        clicked_events.send(MapSpaceClicked {
+                            entity,
+                            col,
+                            row,
+                            button,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Turn a cursor position in the given window into a world position,
+/// using the 2D camera which draws to that window. None if there isn't
+/// one (or the window has gone away).
+fn cursor_to_world(
+    window_id: WindowId,
+    cursor: Vec2,
+    windows: &Windows,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let window = windows.get(window_id)?;
+    // The UI camera draws to the same window, so look for the 2D one by name.
+    let (_camera, camera_transform) = cameras.iter().find(|(camera, _transform)| {
+        camera.window == window_id && camera.name.as_deref() == Some(CAMERA_2D)
+    })?;
+    let window_size = Vec2::new(window.width(), window.height());
+    let from_centre = cursor - window_size / 2.0;
+    let world = camera_transform.compute_matrix() * from_centre.extend(0.0).extend(1.0);
+    Some(Vec2::new(world.x, world.y))
+}
+
+/// The space to pick at col, row: the top one on the highest visible
+/// map layer which has one there.
+fn space_at(
+    col: i32,
+    row: i32,
+    mapengine_map: &crate::map::Map,
+    space_index: &crate::space_index::MapSpaceIndex,
+) -> Option<Entity> {
+    let mut found: Option<(f32, Entity)> = None;
+    for (layer_index, layer) in mapengine_map.layers.iter().enumerate() {
+        if !layer.visible {
+            continue;
+        }
+        if let Some(entity) = space_index.get(layer_index, col, row) {
+            // Later layers win ties, like they do when drawn
+            if found.map_or(true, |(z, _entity)| layer.z >= z) {
+                found = Some((layer.z, entity));
+            }
+        }
+    }
+    found.map(|(_z, entity)| entity)
+}
+
+/*----------------------------------------------------------------------------*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads everything the picking system sent since last time, as
+    /// short descriptions in the order the system sends them
+    #[derive(Default)]
+    struct Recorder {
+        unhovered: EventReader<MapSpaceUnhovered>,
+        hovered: EventReader<MapSpaceHovered>,
+        pressed: EventReader<MapSpacePressed>,
+        released: EventReader<MapSpaceReleased>,
+        clicked: EventReader<MapSpaceClicked>,
+    }
+
+    impl Recorder {
+        fn take(&mut self, app: &App) -> Vec<String> {
+            let mut log = Vec::new();
+            let resources = &app.resources;
+            let events = resources.get::<Events<MapSpaceUnhovered>>().unwrap();
+            for e in self.unhovered.iter(&events) {
+                log.push(format!("unhovered {:?} {},{}", e.entity, e.col, e.row));
+            }
+            let events = resources.get::<Events<MapSpaceHovered>>().unwrap();
+            for e in self.hovered.iter(&events) {
+                log.push(format!("hovered {:?} {},{}", e.entity, e.col, e.row));
+            }
+            let events = resources.get::<Events<MapSpacePressed>>().unwrap();
+            for e in self.pressed.iter(&events) {
+                log.push(format!("pressed {:?} {},{}", e.entity, e.col, e.row));
+            }
+            let events = resources.get::<Events<MapSpaceReleased>>().unwrap();
+            for e in self.released.iter(&events) {
+                log.push(format!("released {:?} {},{}", e.entity, e.col, e.row));
+            }
+            let events = resources.get::<Events<MapSpaceClicked>>().unwrap();
+            for e in self.clicked.iter(&events) {
+                log.push(format!("clicked {:?} {},{}", e.entity, e.col, e.row));
+            }
+            log
+        }
+    }
+
+    fn space_a() -> Entity {
+        Entity::new(1)
+    }
+
+    fn space_b() -> Entity {
+        Entity::new(2)
+    }
+
+    /// An 800×600 window, looked at by a 2D camera which has been moved
+    /// to (100,−50) and zoomed out to twice the scale, over a map of
+    /// 32×16 spaces with space A at (0,0) and space B at (1,0).
+    fn picking_app() -> App {
+        let mut map = crate::map::Map::default();
+        map.size_spaces(None, (32, 16));
+        map.layers = vec![crate::map::MapLayer::new("default", 0.0)];
+        let mut space_index = crate::space_index::MapSpaceIndex::default();
+        space_index.place(space_a(), (0, 0, 0), true);
+        space_index.place(space_b(), (0, 1, 0), true);
+        let mut windows = Windows::default();
+        windows.add(Window::new(
+            WindowId::primary(),
+            &WindowDescriptor {
+                width: 800.0,
+                height: 600.0,
+                ..Default::default()
+            },
+            800,
+            600,
+            1.0,
+        ));
+
+        let mut builder = App::build();
+        builder
+            .add_event::<CursorMoved>()
+            .add_event::<CursorLeft>()
+            .add_event::<MouseButtonInput>()
+            .add_event::<MapSpaceHovered>()
+            .add_event::<MapSpaceUnhovered>()
+            .add_event::<MapSpacePressed>()
+            .add_event::<MapSpaceReleased>()
+            .add_event::<MapSpaceClicked>()
+            .add_resource(windows)
+            .add_resource(map)
+            .add_resource(space_index)
+            .init_resource::<MapCursor>()
+            .add_system(map_picking_system.system());
+        let mut app = builder.app;
+        app.world.spawn(Camera2dBundle {
+            global_transform: GlobalTransform {
+                translation: Vec3::new(100.0, -50.0, 999.0),
+                rotation: Quat::identity(),
+                scale: Vec3::new(2.0, 2.0, 1.0),
+            },
+            ..Default::default()
+        });
+        app
+    }
+
+    /// Where the cursor has to be in the window to point at the given
+    /// world position, through the camera in picking_app
+    fn cursor_for(world_x: f32, world_y: f32) -> Vec2 {
+        Vec2::new(
+            400.0 + (world_x - 100.0) / 2.0,
+            300.0 + (world_y + 50.0) / 2.0,
+        )
+    }
+
+    /// The centre of space (col, 0)
+    fn over_space(col: i32) -> Vec2 {
+        cursor_for(col as f32 * 32.0 + 16.0, -8.0)
+    }
+
+    fn move_cursor(app: &mut App, position: Vec2) {
+        app.resources
+            .get_mut::<Events<CursorMoved>>()
+            .unwrap()
+            .send(CursorMoved {
+                id: WindowId::primary(),
+                position,
+            });
+    }
+
+    fn leave_window(app: &mut App) {
+        app.resources
+            .get_mut::<Events<CursorLeft>>()
+            .unwrap()
+            .send(CursorLeft {
+                id: WindowId::primary(),
+            });
+    }
+
+    fn left_button(app: &mut App, state: ElementState) {
+        app.resources
+            .get_mut::<Events<MouseButtonInput>>()
+            .unwrap()
+            .send(MouseButtonInput {
+                button: MouseButton::Left,
+                state,
+            });
+    }
+
+    #[test]
+    fn camera_transform_is_used() {
+        let mut app = picking_app();
+        move_cursor(&mut app, over_space(1));
+        app.update();
+        let map_cursor = app.resources.get::<MapCursor>().unwrap();
+        assert_eq!(map_cursor.position, Some(Vec2::new(48.0, -8.0)));
+        assert_eq!(map_cursor.cell, Some((1, 0)));
+        assert_eq!(map_cursor.hovered, Some(space_b()));
+    }
+
+    #[test]
+    fn hovered_then_unhovered() {
+        let mut app = picking_app();
+        let mut recorder = Recorder::default();
+
+        move_cursor(&mut app, over_space(0));
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![format!("hovered {:?} 0,0", space_a())]
+        );
+
+        // Moving within the same space says nothing new
+        move_cursor(&mut app, cursor_for(2.0, -2.0));
+        app.update();
+        assert!(recorder.take(&app).is_empty());
+
+        move_cursor(&mut app, over_space(1));
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![
+                format!("unhovered {:?} 0,0", space_a()),
+                format!("hovered {:?} 1,0", space_b()),
+            ]
+        );
+
+        // An empty cell
+        move_cursor(&mut app, over_space(5));
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![format!("unhovered {:?} 1,0", space_b())]
+        );
+    }
+
+    #[test]
+    fn leaving_the_window_unhovers() {
+        let mut app = picking_app();
+        let mut recorder = Recorder::default();
+
+        move_cursor(&mut app, over_space(0));
+        app.update();
+        recorder.take(&app);
+
+        leave_window(&mut app);
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![format!("unhovered {:?} 0,0", space_a())]
+        );
+        let map_cursor = app.resources.get::<MapCursor>().unwrap();
+        assert_eq!(map_cursor.hovered, None);
+        assert_eq!(map_cursor.position, None);
+    }
+
+    #[test]
+    fn pressed_released_then_clicked() {
+        let mut app = picking_app();
+        let mut recorder = Recorder::default();
+
+        move_cursor(&mut app, over_space(0));
+        left_button(&mut app, ElementState::Pressed);
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![
+                format!("hovered {:?} 0,0", space_a()),
+                format!("pressed {:?} 0,0", space_a()),
+            ]
+        );
+
+        left_button(&mut app, ElementState::Released);
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![
+                format!("released {:?} 0,0", space_a()),
+                format!("clicked {:?} 0,0", space_a()),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_click_when_released_over_another_space() {
+        let mut app = picking_app();
+        let mut recorder = Recorder::default();
+
+        move_cursor(&mut app, over_space(0));
+        left_button(&mut app, ElementState::Pressed);
+        app.update();
+        recorder.take(&app);
+
+        move_cursor(&mut app, over_space(1));
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![
+                format!("unhovered {:?} 0,0", space_a()),
+                format!("hovered {:?} 1,0", space_b()),
+            ]
+        );
+
+        left_button(&mut app, ElementState::Released);
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![format!("released {:?} 1,0", space_b())]
+        );
+    }
+
+    #[test]
+    fn no_click_when_released_outside_the_window() {
+        let mut app = picking_app();
+        let mut recorder = Recorder::default();
+
+        move_cursor(&mut app, over_space(0));
+        left_button(&mut app, ElementState::Pressed);
+        app.update();
+        recorder.take(&app);
+
+        leave_window(&mut app);
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![format!("unhovered {:?} 0,0", space_a())]
+        );
+
+        left_button(&mut app, ElementState::Released);
+        app.update();
+        assert!(recorder.take(&app).is_empty());
+
+        // And back again, with the button already up
+        move_cursor(&mut app, over_space(0));
+        app.update();
+        assert_eq!(
+            recorder.take(&app),
+            vec![format!("hovered {:?} 0,0", space_a())]
+        );
+    }
+}
@@ -0,0 +1,378 @@
+/// This module keeps track of which MapSpace is where, so that game code
+/// can ask "what's at (5,3)?" without searching every space — and so
+/// that when one goes away we still know which part of the map to clear.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+
+// Standard rust things...
+use std::collections::{HashMap, HashSet};
+
+/*----------------------------------------------------------------------------*/
+
+/// Where a space is drawn: which map layer (as an index into
+/// `Map::layers()`), and which column and row.
+pub type SpacePosition = (usize, i32, i32);
+
+/// This global resource maps each MapSpace entity we've drawn to where
+/// it was drawn, and each position back to the entities there.
+///
+/// The engine needs this because once a MapSpace component is removed
+/// (or its entity despawned), it's gone — Bevy can tell us which entity
+/// it was, but not what col and row it had. Game code can use it too,
+/// for picking, pathfinding, and game rules.
+///
+/// It's kept up to date by maptexture_update_system as spaces are
+/// added, moved, and despawned. That runs in the MapEngine's own stage,
+/// after UPDATE, so a system in UPDATE sees the map as it was drawn at
+/// the end of the last frame. Spaces which aren't drawn at all (on a
+/// map layer which doesn't exist, or rejected for being out of bounds)
+/// aren't in here, and clamped ones are where they were drawn.
+///
+/// Lookups take a map layer as an index into `Map::layers()`: 0 is the
+/// bottom layer, which is where spaces go by default, and
+/// `map.layer_index(Some("objects"))` finds the others.
+///
+/// For example, in a system with `index: Res<MapSpaceIndex>`:
+///
+/// ```ignore
+/// if let Some(entity) = index.get(0, 5, 3) {
+///     // ... do something with the space at (5,3)
+/// }
+/// for (col, row, entity) in index.adjacent(0, 5, 3) {
+///     // ... and the spaces next to it
+/// }
+/// ```
+#[derive(Default)]
+pub struct MapSpaceIndex {
+    positions: HashMap<Entity, SpacePosition>,
+    cells: HashMap<SpacePosition, Vec<Entity>>,
+    /// Positions which have had a space placed in them while something
+    /// else was already there, for overlap_check_system
+    crowded: HashSet<SpacePosition>,
+}
+
+impl MapSpaceIndex {
+    /// The space at col, row on the given layer. If there's more than
+    /// one, this is the one on top (the one which arrived last).
+    pub fn get(&self, layer: usize, col: i32, row: i32) -> Option<Entity> {
+        self.all_at(layer, col, row).last().copied()
+    }
+
+    /// All of the spaces at col, row on the given layer, in the order
+    /// they arrived there (so, bottom first)
+    pub fn all_at(&self, layer: usize, col: i32, row: i32) -> &[Entity] {
+        self.cells
+            .get(&(layer, col, row))
+            .map_or(&[], |entities| entities.as_slice())
+    }
+
+    /// Where the entity was last drawn, if it has been
+    pub fn position_of(&self, entity: Entity) -> Option<SpacePosition> {
+        self.positions.get(&entity).copied()
+    }
+
+    /// Number of spaces in the index
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Every space in the rectangle of cols × rows cells with its top
+    /// left at first_col, first_row, as (col, row, entity). They come
+    /// a row at a time, top to bottom and left to right; spaces sharing
+    /// a cell come in the order they arrived there.
+    ///
+    /// This looks at whichever is fewer: the cells in the rectangle, or
+    /// the cells with something in them. A rectangle running off the
+    /// edge of what an i32 can hold just stops there.
+    pub fn in_rect(
+        &self,
+        layer: usize,
+        first_col: i32,
+        first_row: i32,
+        cols: i32,
+        rows: i32,
+    ) -> Vec<(i32, i32, Entity)> {
+        if cols <= 0 || rows <= 0 {
+            return Vec::new();
+        }
+        self.in_range(
+            layer,
+            (first_col, first_row),
+            (
+                first_col.saturating_add(cols - 1),
+                first_row.saturating_add(rows - 1),
+            ),
+        )
+    }
+
+    /// Every space within radius cells of col, row (in a square, so
+    /// diagonals count), not counting the ones at col, row itself.
+    /// With a radius of 1, that's the eight neighbours.
+    pub fn around(&self, layer: usize, col: i32, row: i32, radius: i32) -> Vec<(i32, i32, Entity)> {
+        if radius <= 0 {
+            return Vec::new();
+        }
+        let mut found = self.in_range(
+            layer,
+            (col.saturating_sub(radius), row.saturating_sub(radius)),
+            (col.saturating_add(radius), row.saturating_add(radius)),
+        );
+        found.retain(|&(near_col, near_row, _entity)| (near_col, near_row) != (col, row));
+        found
+    }
+
+    /// The spaces directly above, left of, right of, and below col, row
+    /// (in that order), for walking around the map one step at a time.
+    pub fn adjacent(&self, layer: usize, col: i32, row: i32) -> Vec<(i32, i32, Entity)> {
+        let neighbours = [
+            (Some(col), row.checked_sub(1)),
+            (col.checked_sub(1), Some(row)),
+            (col.checked_add(1), Some(row)),
+            (Some(col), row.checked_add(1)),
+        ];
+        let mut found = Vec::new();
+        for neighbour in neighbours.iter() {
+            if let (Some(near_col), Some(near_row)) = *neighbour {
+                for &entity in self.all_at(layer, near_col, near_row) {
+                    found.push((near_col, near_row, entity));
+                }
+            }
+        }
+        found
+    }
+
+    /// The spaces from first to last (col, row), both included, picking
+    /// whichever way of finding them looks at fewer cells.
+    fn in_range(
+        &self,
+        layer: usize,
+        first: (i32, i32),
+        last: (i32, i32),
+    ) -> Vec<(i32, i32, Entity)> {
+        let area = (last.0 as i64 - first.0 as i64 + 1) * (last.1 as i64 - first.1 as i64 + 1);
+        if area <= self.cells.len() as i64 {
+            self.walk_range(layer, first, last)
+        } else {
+            self.scan_range(layer, first, last)
+        }
+    }
+
+    /// in_range by looking up each cell in the range in turn
+    fn walk_range(
+        &self,
+        layer: usize,
+        first: (i32, i32),
+        last: (i32, i32),
+    ) -> Vec<(i32, i32, Entity)> {
+        let mut found = Vec::new();
+        for row in first.1..=last.1 {
+            for col in first.0..=last.0 {
+                for &entity in self.all_at(layer, col, row) {
+                    found.push((col, row, entity));
+                }
+            }
+        }
+        found
+    }
+
+    /// in_range by going through every cell with something in it, and
+    /// sorting the ones in the range into the same order as walk_range
+    fn scan_range(
+        &self,
+        layer: usize,
+        first: (i32, i32),
+        last: (i32, i32),
+    ) -> Vec<(i32, i32, Entity)> {
+        let mut cells: Vec<(&SpacePosition, &Vec<Entity>)> = self
+            .cells
+            .iter()
+            .filter(|(&(cell_layer, col, row), _entities)| {
+                cell_layer == layer
+                    && col >= first.0
+                    && col <= last.0
+                    && row >= first.1
+                    && row <= last.1
+            })
+            .collect();
+        cells.sort_by_key(|(&(_layer, col, row), _entities)| (row, col));
+        let mut found = Vec::new();
+        for (&(_layer, col, row), entities) in cells {
+            for &entity in entities {
+                found.push((col, row, entity));
+            }
+        }
+        found
+    }
+
+    /// Record that the entity is drawn at the given position, replacing
+    /// wherever it was before. If check_crowding is false, sharing the
+    /// cell with another space isn't passed on to the overlap check.
+    pub(crate) fn place(&mut self, entity: Entity, position: SpacePosition, check_crowding: bool) {
+        self.remove(entity);
+        self.positions.insert(entity, position);
+        let entities = self.cells.entry(position).or_default();
+        entities.push(entity);
+        if check_crowding && entities.len() > 1 {
+            self.crowded.insert(position);
+        }
+    }
+
+    /// Forget the entity, giving back where it was, if anywhere.
+    pub(crate) fn remove(&mut self, entity: Entity) -> Option<SpacePosition> {
+        let position = self.positions.remove(&entity)?;
+        if let Some(entities) = self.cells.get_mut(&position) {
+            entities.retain(|&other| other != entity);
+            if entities.is_empty() {
+                self.cells.remove(&position);
+            }
+        }
+        Some(position)
+    }
+
+    /// The positions which have become crowded since this was last called
+    pub(crate) fn take_crowded(&mut self) -> Vec<SpacePosition> {
+        self.crowded.drain().collect()
+    }
+}
+
+/*----------------------------------------------------------------------------*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An index with a few spaces on layer 0, two of them sharing (1,1),
+    /// and one on layer 1 which should never turn up in layer 0 lookups
+    fn sample_index() -> MapSpaceIndex {
+        let mut index = MapSpaceIndex::default();
+        index.place(Entity::new(1), (0, 2, 2), true);
+        index.place(Entity::new(2), (0, 1, 1), true);
+        index.place(Entity::new(3), (0, 0, 1), true);
+        index.place(Entity::new(4), (0, 1, 1), true);
+        index.place(Entity::new(5), (0, 1, 0), true);
+        index.place(Entity::new(6), (0, -1, 1), true);
+        index.place(Entity::new(7), (1, 1, 1), true);
+        index
+    }
+
+    #[test]
+    fn walking_and_scanning_agree() {
+        let index = sample_index();
+        for &(first, last) in &[
+            ((0, 0), (2, 2)),
+            ((-1, 0), (1, 2)),
+            ((1, 1), (1, 1)),
+            ((-5, -5), (5, 5)),
+            ((3, 3), (4, 4)),
+        ] {
+            assert_eq!(
+                index.walk_range(0, first, last),
+                index.scan_range(0, first, last),
+                "from {:?} to {:?}",
+                first,
+                last
+            );
+        }
+        assert_eq!(
+            index.walk_range(0, (-1, 0), (2, 2)),
+            vec![
+                (1, 0, Entity::new(5)),
+                (-1, 1, Entity::new(6)),
+                (0, 1, Entity::new(3)),
+                (1, 1, Entity::new(2)),
+                (1, 1, Entity::new(4)),
+                (2, 2, Entity::new(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn in_rect_uses_either_way_and_gets_the_same_answer() {
+        let index = sample_index();
+        // 3×3 = 9 cells is more than the 6 occupied ones, so this scans...
+        let scanned = index.in_rect(0, 0, 0, 3, 3);
+        // ...and 1×3 is fewer, so these walk.
+        let mut walked = index.in_rect(0, 0, 0, 3, 1);
+        walked.extend(index.in_rect(0, 0, 1, 3, 1));
+        walked.extend(index.in_rect(0, 0, 2, 3, 1));
+        assert_eq!(scanned, walked);
+        assert!(index.in_rect(0, 0, 0, 0, 3).is_empty());
+    }
+
+    #[test]
+    fn get_is_the_top_space() {
+        let index = sample_index();
+        assert_eq!(index.get(0, 1, 1), Some(Entity::new(4)));
+        assert_eq!(index.all_at(0, 1, 1), &[Entity::new(2), Entity::new(4)]);
+        assert_eq!(index.get(1, 1, 1), Some(Entity::new(7)));
+        assert_eq!(index.get(0, 5, 5), None);
+    }
+
+    #[test]
+    fn around_leaves_out_the_centre() {
+        let index = sample_index();
+        assert_eq!(
+            index.around(0, 1, 1, 1),
+            vec![
+                (1, 0, Entity::new(5)),
+                (0, 1, Entity::new(3)),
+                (2, 2, Entity::new(1)),
+            ]
+        );
+        assert!(index.around(0, 1, 1, 0).is_empty());
+    }
+
+    #[test]
+    fn adjacent_goes_up_left_right_down() {
+        let mut index = sample_index();
+        index.place(Entity::new(8), (0, 2, 1), true);
+        index.place(Entity::new(9), (0, 1, 2), true);
+        assert_eq!(
+            index.adjacent(0, 1, 1),
+            vec![
+                (1, 0, Entity::new(5)),
+                (0, 1, Entity::new(3)),
+                (2, 1, Entity::new(8)),
+                (1, 2, Entity::new(9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn extreme_coordinates_dont_overflow() {
+        let mut index = MapSpaceIndex::default();
+        index.place(Entity::new(1), (0, i32::MAX, i32::MAX), true);
+        index.place(Entity::new(2), (0, i32::MIN, i32::MIN), true);
+        let top = vec![(i32::MAX, i32::MAX, Entity::new(1))];
+        let bottom = vec![(i32::MIN, i32::MIN, Entity::new(2))];
+        // Scanning (a big rectangle) and walking (a small one)
+        assert_eq!(
+            index.in_rect(0, i32::MAX - 1, i32::MAX - 1, i32::MAX, i32::MAX),
+            top
+        );
+        assert_eq!(index.in_rect(0, i32::MAX, i32::MAX, 1, 1), top);
+        assert_eq!(index.around(0, i32::MAX - 1, i32::MAX - 1, 5), top);
+        assert_eq!(index.around(0, i32::MIN + 1, i32::MIN + 1, 5), bottom);
+        assert_eq!(index.adjacent(0, i32::MAX, i32::MAX - 1), top);
+        assert!(index.adjacent(0, i32::MIN, i32::MIN).is_empty());
+    }
+
+    #[test]
+    fn moving_and_removing_keep_cells_in_step() {
+        let mut index = sample_index();
+        index.place(Entity::new(4), (0, 2, 2), true);
+        assert_eq!(index.all_at(0, 1, 1), &[Entity::new(2)]);
+        assert_eq!(index.all_at(0, 2, 2), &[Entity::new(1), Entity::new(4)]);
+        assert_eq!(index.remove(Entity::new(2)), Some((0, 1, 1)));
+        assert_eq!(index.get(0, 1, 1), None);
+        assert_eq!(index.position_of(Entity::new(2)), None);
+        assert_eq!(index.len(), 6);
+    }
+}
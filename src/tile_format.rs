@@ -0,0 +1,123 @@
+/// This module handles converting tile textures to the pixel format
+/// of the map texture, so that copying bytes from one to the other
+/// actually copies the right colours.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+// For knowing what format we've got
+use bevy::render::texture::TextureFormat;
+
+/*----------------------------------------------------------------------------*/
+
+/// The map texture is always in this format, so tiles have to be too.
+pub const MAP_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Convert the texture's pixel data to MAP_FORMAT, in place. Does
+/// nothing if it is already in that format.
+///
+/// A note on colour spaces: Bevy's image loader puts grayscale and
+/// 16-bit PNGs in "Unorm"/"Uint" formats, but the values in them are
+/// still the sRGB-encoded ones from the file, so those are copied
+/// across as they are (16-bit values are just cut down to 8 bits).
+/// The 8-bit RGBA formats without "Srgb" in the name, on the other
+/// hand, really are linear, and are encoded to sRGB on the way.
+///
+/// Anything else (floating point, signed, packed formats and so on)
+/// is reported as an error, with the given path to say which tile.
+pub fn convert_to_map_format(
+    texture: &mut Texture,
+    path: &str,
+) -> Result<(), crate::error::MapEngineError> {
+    let data = &texture.data;
+    let converted: Vec<u8> = match texture.format {
+        MAP_FORMAT => return Ok(()),
+        TextureFormat::Bgra8UnormSrgb => convert_pixels(data, 4, |p| [p[2], p[1], p[0], p[3]]),
+        TextureFormat::Rgba8Unorm => {
+            let table = linear_to_srgb_table();
+            convert_pixels(data, 4, |p| {
+                [
+                    table[p[0] as usize],
+                    table[p[1] as usize],
+                    table[p[2] as usize],
+                    p[3],
+                ]
+            })
+        }
+        TextureFormat::Bgra8Unorm => {
+            let table = linear_to_srgb_table();
+            convert_pixels(data, 4, |p| {
+                [
+                    table[p[2] as usize],
+                    table[p[1] as usize],
+                    table[p[0] as usize],
+                    p[3],
+                ]
+            })
+        }
+        TextureFormat::R8Unorm => convert_pixels(data, 1, |p| [p[0], p[0], p[0], 255]),
+        TextureFormat::Rg8Unorm => convert_pixels(data, 2, |p| [p[0], p[0], p[0], p[1]]),
+        TextureFormat::R16Uint => convert_pixels(data, 2, |p| {
+            let v = high_byte(&p[0..2]);
+            [v, v, v, 255]
+        }),
+        TextureFormat::Rg16Uint => convert_pixels(data, 4, |p| {
+            let v = high_byte(&p[0..2]);
+            [v, v, v, high_byte(&p[2..4])]
+        }),
+        TextureFormat::Rgba16Uint => convert_pixels(data, 8, |p| {
+            [
+                high_byte(&p[0..2]),
+                high_byte(&p[2..4]),
+                high_byte(&p[4..6]),
+                high_byte(&p[6..8]),
+            ]
+        }),
+        format => {
+            return Err(crate::error::MapEngineError::UnsupportedFormat {
+                path: path.to_string(),
+                format,
+            })
+        }
+    };
+    texture.data = converted;
+    texture.format = MAP_FORMAT;
+    Ok(())
+}
+
+/// Turn each pixel of `bytes_per_pixel` bytes into an RGBA one. The
+/// result is allocated once, up front, since tiles are converted on
+/// every load and reload.
+fn convert_pixels(
+    data: &[u8],
+    bytes_per_pixel: usize,
+    convert: impl Fn(&[u8]) -> [u8; 4],
+) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(data.len() / bytes_per_pixel * 4);
+    for pixel in data.chunks_exact(bytes_per_pixel) {
+        converted.extend_from_slice(&convert(pixel));
+    }
+    converted
+}
+
+/// 16-bit values are stored as native-endian pairs of bytes. This
+/// gives the top 8 bits of one.
+fn high_byte(pair: &[u8]) -> u8 {
+    (u16::from_ne_bytes([pair[0], pair[1]]) >> 8) as u8
+}
+
+/// Lookup table for encoding linear 8-bit values as sRGB
+fn linear_to_srgb_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let linear = i as f32 / 255.0;
+        let srgb = if linear <= 0.003_130_8 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+        *entry = (srgb * 255.0).round() as u8;
+    }
+    table
+}
@@ -0,0 +1,204 @@
+/// This module holds the tileset manifest: a RON file which gives each
+/// tile a name, a stable numeric id, and whatever properties the game
+/// wants to attach (`walkable`, `move_cost`, `terrain`, and so on).
+/// It also holds the Tileset resource, which is what the manifest
+/// becomes once it has been checked against the loaded tile images.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+
+// For teaching Bevy's asset server how to load the manifest
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+
+// For reading the manifest file itself
+use serde::Deserialize;
+
+// Standard rust things...
+use std::collections::HashMap;
+
+/*----------------------------------------------------------------------------*/
+
+/// A single property value. In the manifest, these are written as
+/// plain RON values: `true`, `3`, `1.5`, or `"forest"`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum TileProperty {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl TileProperty {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TileProperty::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            TileProperty::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Integers are happily returned as floats too.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            TileProperty::Float(f) => Some(*f),
+            TileProperty::Int(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TileProperty::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// One tile as described in the manifest.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileDescription {
+    /// Name to find the tile by, like "grass1"
+    pub name: String,
+    /// Stable numeric id, for save files and the like
+    pub id: u32,
+    /// Asset path of the tile image, like "terrain/grass1.png"
+    /// (or "medieval_tilesheet.png#3" for a tile from a sheet)
+    pub path: String,
+    /// Anything else the game wants to know about this tile
+    #[serde(default)]
+    pub properties: HashMap<String, TileProperty>,
+}
+
+/// The manifest file, as loaded by the asset server. It looks like:
+///
+/// ```ron
+/// (
+///     tiles: [
+///         (
+///             name: "grass1",
+///             id: 1,
+///             path: "terrain/grass1.png",
+///             properties: { "walkable": true, "move_cost": 1, "terrain": "grass" },
+///         ),
+///     ],
+/// )
+/// ```
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "9b9e39ec-88d3-4591-b404-b2040d9ab5a1"]
+pub struct TileManifest {
+    pub tiles: Vec<TileDescription>,
+}
+
+/// Bevy's asset server picks a loader by file extension. Manifests
+/// use `.tileset`, so, for example, `terrain.tileset` next to the
+/// `terrain` tile folder.
+#[derive(Default)]
+pub struct TileManifestLoader;
+
+impl AssetLoader for TileManifestLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let manifest = ron::de::from_bytes::<TileManifest>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(manifest));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tileset"]
+    }
+}
+
+/*----------------------------------------------------------------------------*/
+
+/// Everything we know about one tile, after checking the manifest
+/// against what actually loaded.
+#[derive(Debug, Clone)]
+pub struct TileInfo {
+    pub name: String,
+    pub id: u32,
+    pub path: String,
+    /// Handle to the tile texture, for use in a MapSpace
+    pub handle: Handle<Texture>,
+    pub properties: HashMap<String, TileProperty>,
+}
+
+impl TileInfo {
+    /// Shortcut for `info.properties.get(key)`
+    pub fn property(&self, key: &str) -> Option<&TileProperty> {
+        self.properties.get(key)
+    }
+}
+
+/// This global resource holds the checked manifest. It's empty if there
+/// is no manifest in the MapEngineConfig, and is filled in during the
+/// Verifying state. Game logic can use this to ask questions like "is
+/// the tile in this space walkable?" instead of matching on file names.
+#[derive(Default)]
+pub struct Tileset {
+    tiles: Vec<TileInfo>,
+    by_name: HashMap<String, usize>,
+    by_id: HashMap<u32, usize>,
+    by_handle: HashMap<Handle<Texture>, usize>,
+}
+
+impl Tileset {
+    pub fn get_by_name(&self, name: &str) -> Option<&TileInfo> {
+        self.by_name.get(name).map(|&i| &self.tiles[i])
+    }
+
+    pub fn get_by_id(&self, id: u32) -> Option<&TileInfo> {
+        self.by_id.get(&id).map(|&i| &self.tiles[i])
+    }
+
+    /// Look up a tile by its texture handle — for example, the one
+    /// in a MapSpace.
+    pub fn get_by_handle(&self, handle: &Handle<Texture>) -> Option<&TileInfo> {
+        self.by_handle.get(handle).map(|&i| &self.tiles[i])
+    }
+
+    /// All of the tiles, in manifest order
+    pub fn iter(&self) -> impl Iterator<Item = &TileInfo> {
+        self.tiles.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Add a tile, refusing duplicate names or ids. Used while checking
+    /// the manifest; returns a description of the problem on failure.
+    pub(crate) fn insert(&mut self, info: TileInfo) -> Result<(), String> {
+        if self.by_name.contains_key(&info.name) {
+            return Err(format!("tile name {:?} is used more than once", info.name));
+        }
+        if self.by_id.contains_key(&info.id) {
+            return Err(format!("tile id {:?} is used more than once", info.id));
+        }
+        let index = self.tiles.len();
+        self.by_name.insert(info.name.clone(), index);
+        self.by_id.insert(info.id, index);
+        self.by_handle.insert(info.handle.clone(), index);
+        self.tiles.push(info);
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+/// This module holds the TileRegistry, which maps tile names and ids
+/// to texture handles, so that game code can ask for "grass1" rather
+/// than a handle built from a file path string.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+
+// Standard rust things...
+use std::collections::HashMap;
+
+/*----------------------------------------------------------------------------*/
+
+/// This global resource is filled in at the end of the Verifying state,
+/// so it is empty until the engine is Running.
+///
+/// Each tile can be found by:
+///
+/// * its file stem, like "grass1" for "terrain/grass1.png", or
+///   "medieval_tilesheet#3" for a tile cut from a sheet
+/// * its full asset path, like "terrain/grass1.png"
+/// * its name in the tileset manifest, if there is one
+/// * its numeric id
+///
+/// A name can only belong to one tile. Manifest names come first, so
+/// a tile whose file stem is another tile's manifest name can only be
+/// found by its path. So can tiles which share a stem — two
+/// "grass.png"s in different subfolders, say — because otherwise which
+/// one "grass" found would be down to chance. Either way, a
+/// MapEngineError::DuplicateTile is reported for the name.
+///
+/// Ids come from the manifest where it gives one. Other tiles are
+/// numbered after the highest manifest id, in order of asset path.
+/// That's only stable as long as the set of tiles doesn't change, so
+/// use a manifest if ids need to be saved anywhere.
+#[derive(Default)]
+pub struct TileRegistry {
+    by_name: HashMap<String, Handle<Texture>>,
+    by_id: HashMap<u32, Handle<Texture>>,
+    ids: HashMap<Handle<Texture>, u32>,
+}
+
+impl TileRegistry {
+    /// Get the tile with the given name, if there is one
+    pub fn get(&self, name: &str) -> Option<Handle<Texture>> {
+        self.by_name.get(name).cloned()
+    }
+
+    /// Get the tile with the given id, if there is one
+    pub fn get_by_id(&self, id: u32) -> Option<Handle<Texture>> {
+        self.by_id.get(&id).cloned()
+    }
+
+    /// Like get(), but with an error naming the tile that doesn't exist,
+    /// so a typo can be reported (or `?`-ed) right where it happens.
+    pub fn handle(&self, name: &str) -> Result<Handle<Texture>, crate::error::MapEngineError> {
+        self.get(name)
+            .ok_or_else(|| crate::error::MapEngineError::UnknownTile {
+                name: name.to_string(),
+            })
+    }
+
+    /// Find the id of the tile with the given handle
+    pub fn id_of(&self, handle: &Handle<Texture>) -> Option<u32> {
+        self.ids.get(handle).copied()
+    }
+
+    /// All of the names that can be looked up
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.by_name.keys().map(|name| name.as_str())
+    }
+
+    /// Number of distinct tiles
+    pub fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+
+    /// Add a tile made in code — a tinted variant, generated noise, a
+    /// team-coloured flag, whatever — and get back a handle for use in
+    /// a MapSpace. It can also be found by name or id afterwards, like
+    /// any other tile; it gets the next unused id.
+    ///
+    /// This only works once the engine is Running, because until then
+    /// we don't know what size tiles need to be. The texture has to fit
+    /// the same way loaded tiles do: exactly the size of a space (see
+    /// `map.space_size()`), or, if the space size was configured, at
+    /// least that big, overhanging the spaces around. The name can't
+    /// already be in use. It will be converted to the map's pixel format
+    /// if it isn't in it already.
+    ///
+    /// For example, in a system with `mut textures: ResMut<Assets<Texture>>`,
+    /// `mut map: ResMut<Map>` and `mut tile_registry: ResMut<TileRegistry>`:
+    ///
+    /// ```ignore
+    /// let flag = make_flag_texture(team_colour);
+    /// let handle = tile_registry.register("red_flag", flag, &mut textures, &mut map)?;
+    /// ```
+    pub fn register(
+        &mut self,
+        name: &str,
+        mut texture: Texture,
+        textures: &mut Assets<Texture>,
+        map: &mut crate::map::Map,
+    ) -> Result<Handle<Texture>, crate::error::MapEngineError> {
+        if map.space_width_pixels == 0 || map.space_height_pixels == 0 {
+            return Err(crate::error::MapEngineError::NotRunning);
+        }
+        if self.by_name.contains_key(name) {
+            return Err(crate::error::MapEngineError::DuplicateTile {
+                name: name.to_string(),
+            });
+        }
+        let expected = (
+            map.space_width_pixels as u32,
+            map.space_height_pixels as u32,
+        );
+        let found = (texture.size.width, texture.size.height);
+        if !map.tile_fits(found.0, found.1) {
+            return Err(crate::error::MapEngineError::SizeMismatch {
+                path: name.to_string(),
+                expected,
+                found,
+            });
+        }
+        if texture.size.depth != 1 {
+            return Err(crate::error::MapEngineError::NotTwoDimensional {
+                path: name.to_string(),
+            });
+        }
+
+        crate::tile_format::convert_to_map_format(&mut texture, name)?;
+        // It might overhang further than any tile so far.
+        map.note_tile_size(found.0, found.1);
+
+        let id = self.by_id.keys().max().map_or(0, |id| id + 1);
+        let handle = textures.add(texture);
+        self.insert(id, handle.clone());
+        self.add_name(name, &handle)?;
+        Ok(handle)
+    }
+
+    /// Add a tile under the given id, without any names yet.
+    pub(crate) fn insert(&mut self, id: u32, handle: Handle<Texture>) {
+        self.ids.insert(handle.clone(), id);
+        self.by_id.insert(id, handle);
+    }
+
+    /// Give a tile another name. If the name already belongs to a
+    /// different tile, it stays with that one, and this is an error.
+    pub(crate) fn add_name(
+        &mut self,
+        name: &str,
+        handle: &Handle<Texture>,
+    ) -> Result<(), crate::error::MapEngineError> {
+        match self.by_name.get(name) {
+            Some(existing) if existing != handle => {
+                Err(crate::error::MapEngineError::DuplicateTile {
+                    name: name.to_string(),
+                })
+            }
+            _ => {
+                self.by_name.insert(name.to_string(), handle.clone());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The file stem of an asset path, keeping any label: "terrain/grass1.png"
+/// becomes "grass1", and "medieval_tilesheet.png#3" becomes "medieval_tilesheet#3".
+pub(crate) fn tile_stem(path: &str) -> String {
+    let (file, label) = match path.find('#') {
+        Some(i) => (&path[..i], &path[i..]),
+        None => (path, ""),
+    };
+    let stem = std::path::Path::new(file)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    stem + label
+}
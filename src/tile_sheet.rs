@@ -0,0 +1,236 @@
+/// This module holds the description of a tile sheet — a single image
+/// with many tiles laid out on a grid — and the code to cut such a
+/// sheet into separate tile textures.
+/*----------------------------------------------------------------------------*/
+//
+
+// This is the basic Bevy game engine stuff
+use bevy::prelude::*;
+// These are used for creating the sliced tile textures
+use bevy::render::texture::Extent3d;
+
+/*----------------------------------------------------------------------------*/
+
+/// Describes a tile sheet and the grid its tiles are laid out on.
+///
+/// Many sheets don't pack their tiles edge to edge, so besides the tile
+/// size and count this takes a margin (a border of that many pixels
+/// all around the sheet), spacing (a gutter of that many pixels between
+/// neighbouring tiles), and an offset (extra pixels skipped at the top
+/// left before the margin, for sheets with a header or similar). These
+/// all default to 0; set them with the `with_` methods.
+///
+/// Each tile cut from the sheet becomes its own texture. These are
+/// found by the sheet's path plus a label giving the tile's index,
+/// counting left-to-right and then top-to-bottom from 0. So, for
+/// example, the third tile on the first row of `medieval_tilesheet.png`
+/// is `asset_server.get_handle("medieval_tilesheet.png#2")`.
+#[derive(Debug, Clone)]
+pub struct TileSheet {
+    /// Path to the sheet image, relative to the assets folder
+    pub path: String,
+    /// Width of each tile in pixels
+    pub tile_width: u32,
+    /// Height of each tile in pixels
+    pub tile_height: u32,
+    /// Number of tiles across the sheet
+    pub columns: u32,
+    /// Number of tiles down the sheet
+    pub rows: u32,
+    /// Border around the outside of the sheet, in pixels
+    pub margin: u32,
+    /// Gap between neighbouring tiles, in pixels
+    pub spacing: u32,
+    /// Extra pixels to skip on the left before the first column
+    pub offset_x: u32,
+    /// Extra pixels to skip at the top before the first row
+    pub offset_y: u32,
+}
+
+impl TileSheet {
+    /// Describe a sheet at the given path (either a String or a str),
+    /// with tiles of the given size in pixels arranged columns × rows.
+    pub fn new<S: Into<String>>(
+        path: S,
+        tile_width: u32,
+        tile_height: u32,
+        columns: u32,
+        rows: u32,
+    ) -> TileSheet {
+        TileSheet {
+            path: path.into(),
+            tile_width,
+            tile_height,
+            columns,
+            rows,
+            margin: 0,
+            spacing: 0,
+            offset_x: 0,
+            offset_y: 0,
+        }
+    }
+
+    /// Set the border around the outside of the sheet
+    pub fn with_margin(mut self, margin: u32) -> TileSheet {
+        self.margin = margin;
+        self
+    }
+
+    /// Set the gap between neighbouring tiles
+    pub fn with_spacing(mut self, spacing: u32) -> TileSheet {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Set the extra pixels skipped at the top left before the grid starts
+    pub fn with_offset(mut self, offset_x: u32, offset_y: u32) -> TileSheet {
+        self.offset_x = offset_x;
+        self.offset_y = offset_y;
+        self
+    }
+
+    /// The asset path which refers to the tile at the given index.
+    /// Pass this to `asset_server.get_handle()` to use the tile.
+    pub fn tile_path(&self, index: u32) -> String {
+        format!("{}#{}", self.path, index)
+    }
+
+    /// Total number of tiles on the sheet
+    pub fn tile_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// The exact size in pixels (width, height) a sheet with this layout
+    /// should be. The margin is on both sides; spacing is only between
+    /// tiles, not after the last one.
+    pub fn expected_size(&self) -> (u32, u32) {
+        (
+            self.offset_x
+                + 2 * self.margin
+                + self.columns * self.tile_width
+                + self.columns.saturating_sub(1) * self.spacing,
+            self.offset_y
+                + 2 * self.margin
+                + self.rows * self.tile_height
+                + self.rows.saturating_sub(1) * self.spacing,
+        )
+    }
+
+    /// Pixel position of the top left of the tile in the given column and row
+    fn tile_origin(&self, col: u32, row: u32) -> (u32, u32) {
+        (
+            self.offset_x + self.margin + col * (self.tile_width + self.spacing),
+            self.offset_y + self.margin + row * (self.tile_height + self.spacing),
+        )
+    }
+}
+
+/*----------------------------------------------------------------------------*/
+
+/// Cuts the sheet texture up into one texture per tile, in index order.
+///
+/// Like the drawing code in blend, this works on the bytes in the
+/// texture's data Vec rather than actual GPU textures. The sheet
+/// texture must already have been checked against expected_size().
+pub(crate) fn slice_tile_sheet(sheet: &TileSheet, sheet_texture: &Texture) -> Vec<Texture> {
+    let format_size = sheet_texture.format.pixel_size();
+    let sheet_width = sheet_texture.size.width as usize;
+    let tile_width = sheet.tile_width as usize;
+    let tile_height = sheet.tile_height as usize;
+
+    let mut tiles = Vec::with_capacity(sheet.tile_count() as usize);
+    for row in 0..sheet.rows {
+        for col in 0..sheet.columns {
+            let (rect_x, rect_y) = sheet.tile_origin(col, row);
+            let (rect_x, rect_y) = (rect_x as usize, rect_y as usize);
+            let mut data = Vec::with_capacity(tile_width * tile_height * format_size);
+            for sheet_y in rect_y..rect_y + tile_height {
+                let begin = (sheet_y * sheet_width + rect_x) * format_size;
+                let end = begin + tile_width * format_size;
+                data.extend_from_slice(&sheet_texture.data[begin..end]);
+            }
+            tiles.push(Texture::new(
+                Extent3d::new(sheet.tile_width, sheet.tile_height, 1),
+                sheet_texture.dimension,
+                data,
+                sheet_texture.format,
+            ));
+        }
+    }
+    tiles
+}
+
+/*----------------------------------------------------------------------------*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::texture::{TextureDimension, TextureFormat};
+
+    /// 3×2 tiles of 2×2 pixels, with everything that moves them around
+    /// set to something other than 0
+    fn sheet() -> TileSheet {
+        TileSheet::new("sheet.png", 2, 2, 3, 2)
+            .with_margin(1)
+            .with_spacing(1)
+            .with_offset(2, 1)
+    }
+
+    /// A sheet texture where each pixel says where it is: red is x,
+    /// green is y
+    fn sheet_texture(width: u32, height: u32) -> Texture {
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&[x as u8, y as u8, 0, 255]);
+            }
+        }
+        Texture::new(
+            Extent3d::new(width, height, 1),
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    #[test]
+    fn expected_size_counts_offset_margin_and_spacing() {
+        // Width: offset 2 + margins 1+1 + tiles 3×2 + spacing 2×1
+        // Height: offset 1 + margins 1+1 + tiles 2×2 + spacing 1×1
+        assert_eq!(sheet().expected_size(), (12, 8));
+        // With nothing but tiles, it's just the tiles
+        assert_eq!(
+            TileSheet::new("plain.png", 32, 16, 4, 3).expected_size(),
+            (128, 48)
+        );
+    }
+
+    #[test]
+    fn tile_origin_skips_offset_margin_and_spacing() {
+        assert_eq!(sheet().tile_origin(0, 0), (3, 2));
+        assert_eq!(sheet().tile_origin(1, 0), (6, 2));
+        assert_eq!(sheet().tile_origin(2, 1), (9, 5));
+    }
+
+    #[test]
+    fn slicing_cuts_the_right_pixels() {
+        let sheet = sheet();
+        let (width, height) = sheet.expected_size();
+        let tiles = slice_tile_sheet(&sheet, &sheet_texture(width, height));
+        assert_eq!(tiles.len(), 6);
+        assert!(tiles
+            .iter()
+            .all(|tile| (tile.size.width, tile.size.height) == (2, 2)));
+
+        // Index 4 is the middle tile of the second row, from (6,5)
+        let pixels: Vec<(u8, u8)> = tiles[4]
+            .data
+            .chunks_exact(4)
+            .map(|pixel| (pixel[0], pixel[1]))
+            .collect();
+        assert_eq!(pixels, vec![(6, 5), (7, 5), (6, 6), (7, 6)]);
+
+        // And the first, from (3,2)
+        assert_eq!(&tiles[0].data[0..4], &[3, 2, 0, 255]);
+    }
+}
@@ -7,7 +7,17 @@
 use bevy::prelude::*;
 
 // Used to tell if assets are loaded ... see check_tiles_loaded_system()
-use bevy::asset::LoadState;
+// And HandleId is for matching the tileset manifest up with loaded tiles
+use bevy::asset::{HandleId, LoadState};
+
+// Standard rust things...
+use std::collections::{BTreeMap, HashMap};
+
+// For reporting problems, rather than just exiting
+use crate::error::{enter_error_state, MapEngineError, MapEngineErrors};
+
+// For reporting how loading is going
+use crate::load_progress::{MapEngineLoadEvent, MapEngineLoadProgress, TileFileStatus};
 
 /// Our list of handles to tile images is stored as a global
 /// Bevy resource so we can use them in various systems. In Bevy,
@@ -16,6 +26,37 @@ use bevy::asset::LoadState;
 #[derive(Default)]
 pub struct MapEngineTileHandles {
     handles: Vec<HandleUntyped>,
+    /// Sheets waiting to be sliced into tiles, with handles to their images
+    sheets: Vec<(crate::TileSheet, Handle<Texture>)>,
+    /// The tileset manifest, if there is one
+    manifest: Option<Handle<crate::tile_manifest::TileManifest>>,
+    /// The asset server doesn't know the paths of tiles cut from sheets,
+    /// so we keep track of those here.
+    sheet_tile_paths: HashMap<HandleId, String>,
+}
+
+impl MapEngineTileHandles {
+    /// The ids of everything we're waiting on: tiles, sheets, and manifest
+    fn ids(&self) -> impl Iterator<Item = HandleId> + '_ {
+        self.handles
+            .iter()
+            .map(|handle| handle.id)
+            .chain(self.sheets.iter().map(|(_sheet, handle)| handle.id))
+            .chain(self.manifest.iter().map(|handle| handle.id))
+    }
+
+    /// Get the asset path of one of our tiles (or sheets, or the manifest),
+    /// for messages and for the TileRegistry. Anything we somehow don't
+    /// know the path of just gets the id.
+    fn path(&self, asset_server: &AssetServer, id: HandleId) -> String {
+        if let Some(path) = self.sheet_tile_paths.get(&id) {
+            return path.clone();
+        }
+        match asset_server.get_handle_path(id) {
+            Some(asset_path) => asset_path.path().to_string_lossy().into_owned(),
+            None => format!("{:?}", id),
+        }
+    }
 }
 
 /// This function is a "system" — see the App builder in main(), below.
@@ -31,17 +72,96 @@ pub fn load_tiles_system(
     asset_server: Res<AssetServer>,
     map_engine_config: Res<crate::MapEngineConfig>,
     mut tilehandles: ResMut<MapEngineTileHandles>,
+    mut state: ResMut<State<crate::MapEngineState>>,
+    mut errors: ResMut<MapEngineErrors>,
+    mut error_events: ResMut<Events<MapEngineError>>,
+    mut progress: ResMut<MapEngineLoadProgress>,
+    mut load_events: ResMut<Events<MapEngineLoadEvent>>,
 ) {
+    // We may be here again after an error, so start fresh.
+    *tilehandles = MapEngineTileHandles::default();
+    *progress = MapEngineLoadProgress::default();
+
     // The asset server defaults to looking in the `assets` directory.
     // This call loads everything in the given subfolder as our
     // tile images and stores the list of handles in the global resource.
-    match asset_server.load_folder(&map_engine_config.tile_folder) {
-        Ok(handles) => tilehandles.handles = handles,
-        Err(err) => {
-            eprintln!("Error: Problem loading tile textures ({:?})", err);
-            std::process::exit(1);
+    // An empty folder name means all the tiles come from sheets.
+    if !map_engine_config.tile_folder.is_empty() {
+        match asset_server.load_folder(&map_engine_config.tile_folder) {
+            Ok(handles) => tilehandles.handles = handles,
+            Err(err) => {
+                // Anyone watching the load should still see it start (and
+                // fail), rather than an error out of nowhere, so count the
+                // folder itself as one file which failed.
+                let folder = map_engine_config.tile_folder.clone();
+                progress.files = vec![TileFileStatus {
+                    path: folder.clone(),
+                    state: LoadState::Failed,
+                }];
+                progress.total = 1;
+                progress.failed = 1;
+                load_events.send(MapEngineLoadEvent::Started { total: 1 });
+                load_events.send(MapEngineLoadEvent::Failed {
+                    paths: vec![folder],
+                });
+                enter_error_state(
+                    MapEngineError::MissingFolder {
+                        folder: map_engine_config.tile_folder.clone(),
+                        reason: format!("{:?}", err),
+                    },
+                    &mut state,
+                    &mut errors,
+                    &mut error_events,
+                );
+                return;
+            }
+        }
+    }
+
+    // Tile sheets are loaded as single images here, and then cut into
+    // tiles in verify_tiles_system once they're available.
+    tilehandles.sheets = map_engine_config
+        .tile_sheets
+        .iter()
+        .map(|sheet| (sheet.clone(), asset_server.load(sheet.path.as_str())))
+        .collect();
+
+    // If asked, have the asset server watch for changed files, so that
+    // tile_reload_system can redraw the map when a tile is edited.
+    if map_engine_config.hot_reload {
+        if let Err(err) = asset_server.watch_for_changes() {
+            eprintln!("Warning: can't watch tiles for changes ({:?})", err);
         }
     }
+
+    // And the manifest, if any, is just another asset to wait for.
+    tilehandles.manifest = map_engine_config
+        .manifest
+        .as_ref()
+        .map(|manifest| asset_server.load(manifest.as_str()));
+
+    // Now we know everything we're waiting for, so we can start tracking it.
+    progress.files = tilehandles
+        .ids()
+        .map(|id| TileFileStatus {
+            path: tilehandles.path(&asset_server, id),
+            state: LoadState::NotLoaded,
+        })
+        .collect();
+    progress.total = progress.files.len();
+    load_events.send(MapEngineLoadEvent::Started {
+        total: progress.total,
+    });
+}
+
+/// This system runs once on entering the Error state. It lets go of all
+/// of our handles to the tile assets. Once nothing holds a handle to an
+/// asset, Bevy frees it (at the start of the next frame) and forgets it
+/// was ever loaded — so when load_tiles_system asks for the same files
+/// again on a retry, they're read fresh from disk, fixes and all, rather
+/// than the broken versions being handed straight back.
+pub fn release_tiles_system(mut tilehandles: ResMut<MapEngineTileHandles>) {
+    *tilehandles = MapEngineTileHandles::default();
 }
 
 /// This system is configured to run as part of the game loop while in
@@ -51,93 +171,600 @@ pub fn load_tiles_system(
 /// Here, you can see that in addition to the resources the load system
 /// uses we also get the State resource. And since we don't modify the
 /// tilehandles here, that resource is not mutable.
+///
+/// As it goes, it keeps the MapEngineLoadProgress resource up to date,
+/// and sends a MapEngineLoadEvent whenever anything changes.
+#[allow(clippy::too_many_arguments)]
 pub fn wait_for_tile_load_system(
     mut state: ResMut<State<crate::MapEngineState>>,
     tilehandles: ResMut<MapEngineTileHandles>,
     asset_server: Res<AssetServer>,
+    mut errors: ResMut<MapEngineErrors>,
+    mut error_events: ResMut<Events<MapEngineError>>,
+    mut progress: ResMut<MapEngineLoadProgress>,
+    mut load_events: ResMut<Events<MapEngineLoadEvent>>,
 ) {
-    // Note that this is pretty much always going to be "NotLoaded" until it becomes "Loaded".
-    // The "Loading" state is unlikely because get_group_load_state returns not loaded if _any_ are.
-    match asset_server.get_group_load_state(tilehandles.handles.iter().map(|handle| handle.id)) {
-        LoadState::NotLoaded => println!("Loading tile textures..."),
-        LoadState::Loading => println!("Loading tile textures..."),
-        LoadState::Loaded => {
-            println!("Terrain textures loaded!");
-            // Finally advance the State
-            state.set_next(crate::MapEngineState::Verifying).unwrap();
-        }
-        LoadState::Failed => {
-            eprintln!("Failed to load tile textures!");
-            std::process::exit(1)
+    // Check on each file. The list of files is in the same order
+    // as the ids, because that's how load_tiles_system made it.
+    let mut changed = false;
+    for (file, id) in progress.files.iter_mut().zip(tilehandles.ids()) {
+        let file_state = asset_server.get_load_state(id);
+        if file_state != file.state {
+            file.state = file_state;
+            changed = true;
         }
     }
+    // (With nothing at all to load, there's nothing to wait for, so
+    // carry on to Verifying, which will report the lack of tiles.)
+    if !changed && progress.total > 0 {
+        return;
+    }
+
+    progress.loaded = progress
+        .files
+        .iter()
+        .filter(|file| file.state == LoadState::Loaded)
+        .count();
+    progress.failed = progress
+        .files
+        .iter()
+        .filter(|file| file.state == LoadState::Failed)
+        .count();
+    load_events.send(MapEngineLoadEvent::Progress {
+        loaded: progress.loaded,
+        failed: progress.failed,
+        total: progress.total,
+    });
+
+    // We wait until everything is done one way or another, even if
+    // something has already failed, so we can report all the failures.
+    if progress.loaded + progress.failed < progress.total {
+        return;
+    }
+    if progress.failed == 0 {
+        load_events.send(MapEngineLoadEvent::Finished {
+            total: progress.total,
+        });
+        // Finally advance the State
+        state.set_next(crate::MapEngineState::Verifying).unwrap();
+    } else {
+        let paths: Vec<String> = progress.failed_paths().map(String::from).collect();
+        load_events.send(MapEngineLoadEvent::Failed {
+            paths: paths.clone(),
+        });
+        enter_error_state(
+            MapEngineError::LoadFailed { paths },
+            &mut state,
+            &mut errors,
+            &mut error_events,
+        );
+    }
 }
 
 /// A system which checks to make sure all of the loaded tiles are
 /// valid and then advances to the next game State (Running).
-/// A more advanced version of this could go to an Error state
-/// instead of existing on failure. Combined with dynamic asset
-/// loading, that could make it possible to actually fix the
-/// problem and resume.
+/// On failure, it goes to the Error state instead. From there, the
+/// plugin user can send a MapEngineRetryLoad event once the problem
+/// is fixed, and we'll start over from Loading.
+#[allow(clippy::too_many_arguments)]
 pub fn verify_tiles_system(
     mut state: ResMut<State<crate::MapEngineState>>,
-    tilehandles: ResMut<MapEngineTileHandles>,
-    textures: Res<Assets<Texture>>,
+    mut tilehandles: ResMut<MapEngineTileHandles>,
+    mut textures: ResMut<Assets<Texture>>,
     mut mapengine_map: ResMut<crate::map::Map>,
+    manifests: Res<Assets<crate::tile_manifest::TileManifest>>,
+    mut tileset: ResMut<crate::tile_manifest::Tileset>,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<MapEngineErrors>,
+    mut error_events: ResMut<Events<MapEngineError>>,
+    mut registry: ResMut<crate::tile_registry::TileRegistry>,
+    map_engine_config: Res<crate::MapEngineConfig>,
 ) {
-    // This crazy code does this:
-    //
-    // 1. Gets the widths, heights, and depths of all textures
-    // 2. Sets mapengine_map height and width
-    // 3. Errors if any tiles are differently-sized
-    // 4. Errors if any depth is anything but 1
-    // 5. And if all that succeeds, moves on to Running
-    //
-    // We could add other verification here as well, of course.
-    let widths = tilehandles
-        .handles
-        .iter()
-        .map(|handle| textures.get(handle).unwrap().size.width)
-        .collect::<Vec<u32>>();
-    let heights = tilehandles
-        .handles
-        .iter()
-        .map(|handle| textures.get(handle).unwrap().size.height)
-        .collect::<Vec<u32>>();
-    let depths = tilehandles
+    // First, cut any tile sheets up into separate tile textures.
+    // Each one is stored under the sheet's path plus a "#index" label,
+    // so `asset_server.get_handle("sheet.png#3")` finds it, just like
+    // a tile loaded from its own file. We keep the (strong) handles
+    // with the rest so the tiles stay around.
+    for (sheet, sheet_handle) in tilehandles.sheets.clone() {
+        match cut_tile_sheet(&sheet, &sheet_handle, &mut textures) {
+            Ok(handles) => {
+                for (index, handle) in handles.iter().enumerate() {
+                    tilehandles
+                        .sheet_tile_paths
+                        .insert(handle.id, sheet.tile_path(index as u32));
+                    tilehandles.handles.push(handle.clone_untyped());
+                }
+            }
+            Err(error) => {
+                enter_error_state(error, &mut state, &mut errors, &mut error_events);
+                return;
+            }
+        }
+    }
+
+    // The tile folder might have other kinds of asset in it as well — the
+    // tileset manifest, say, or anything else Bevy has a loader for. They
+    // loaded fine, but they aren't textures, so they aren't tiles. Warn
+    // about them and leave them out, rather than tripping over them below.
+    let (tiles, others): (Vec<HandleUntyped>, Vec<HandleUntyped>) = tilehandles
         .handles
-        .iter()
-        .map(|handle| textures.get(handle).unwrap().size.depth)
-        .collect::<Vec<u32>>();
+        .drain(..)
+        .partition(|handle| textures.get(handle).is_some());
+    for other in others.iter() {
+        eprintln!(
+            "Warning: {} isn't an image, so it isn't used as a tile.",
+            tilehandles.path(&asset_server, other.id)
+        );
+    }
+    tilehandles.handles = tiles;
 
-    mapengine_map.space_width_pixels = widths[0] as usize;
-    mapengine_map.space_height_pixels = widths[0] as usize;
+    if tilehandles.handles.is_empty() {
+        enter_error_state(
+            MapEngineError::NoTiles,
+            &mut state,
+            &mut errors,
+            &mut error_events,
+        );
+        return;
+    }
 
-    if widths
-        .iter()
-        .any(|&w| w != mapengine_map.space_width_pixels as u32)
-    {
-        eprintln!("Error! All tile textures must be the same width (at least one isn't).");
-        std::process::exit(1)
+    // Next, make sure every tile is in the same pixel format as the map
+    // texture, converting the ones that aren't (or failing, if we can't).
+    // We only touch the ones that need it, because get_mut counts as
+    // modifying the texture, which would make tile_reload_system redraw.
+    for handle in tilehandles.handles.iter() {
+        if textures.get(handle).unwrap().format == crate::tile_format::MAP_FORMAT {
+            continue;
+        }
+        let path = tilehandles.path(&asset_server, handle.id);
+        if let Err(error) =
+            crate::tile_format::convert_to_map_format(textures.get_mut(handle).unwrap(), &path)
+        {
+            enter_error_state(error, &mut state, &mut errors, &mut error_events);
+            return;
+        }
     }
-    if heights
+
+    // Then, work out the size of a space and check every tile against it
+    // (see fit_tiles_to_spaces, below). Everything loaded is a texture by
+    // now, since anything else was left out above.
+    let sizes = tilehandles
+        .handles
         .iter()
-        .any(|&h| h != mapengine_map.space_height_pixels as u32)
-    {
-        eprintln!("Error! All tile textures must be the same height (at least one isn't).");
-        std::process::exit(1)
-    }
-    if depths.iter().any(|&d| d != 1) {
-        eprintln!("Error! At least of the tile textures isn't two-dimensional!");
-        std::process::exit(1)
+        .map(|handle| {
+            let size = textures.get(handle).unwrap().size;
+            (size.width, size.height, size.depth)
+        })
+        .collect::<Vec<(u32, u32, u32)>>();
+    if let Err(error) = fit_tiles_to_spaces(
+        &mut mapengine_map,
+        map_engine_config.space_size,
+        &sizes,
+        |i| tilehandles.path(&asset_server, tilehandles.handles[i].id),
+    ) {
+        enter_error_state(error, &mut state, &mut errors, &mut error_events);
+        return;
     }
 
     println!(
-        "{:?} tile textures of size {:?}×{:?} found.",
-        widths.len(),
+        "{:?} tile textures for spaces of size {:?}×{:?} found.",
+        sizes.len(),
         mapengine_map.space_width_pixels,
         mapengine_map.space_height_pixels
     );
 
+    // Finally, if there is a manifest, check it against the tiles which
+    // actually loaded, and turn it into the Tileset resource.
+    if let Some(manifest_handle) = &tilehandles.manifest {
+        let manifest = manifests.get(manifest_handle).unwrap();
+        match build_tileset(manifest, &tilehandles.handles) {
+            Ok(checked) => {
+                println!("Tileset manifest describes {:?} tiles.", checked.len());
+                *tileset = checked;
+            }
+            Err(problem) => {
+                enter_error_state(
+                    MapEngineError::Manifest { problem },
+                    &mut state,
+                    &mut errors,
+                    &mut error_events,
+                );
+                return;
+            }
+        }
+    }
+
+    // And with everything checked, record all of the tiles in the registry.
+    // Names that clash are reported, but aren't a reason to stop: every
+    // tile can still be found by its path.
+    let (built, problems) = build_registry(&tilehandles, &asset_server, &tileset);
+    for error in problems {
+        crate::error::report_error(error, &mut errors, &mut error_events);
+    }
+    *registry = built;
+
     state.set_next(crate::MapEngineState::Running).unwrap();
 }
+
+/// This crazy code does this:
+///
+/// 1. Sets the map's space size (from the config, if it's there, and
+///    otherwise from the first tile)
+/// 2. Errors if any tiles don't fit (by finding the first odd one):
+///    they must all be the same size, or, if the space size is
+///    configured, at least that size
+/// 3. Errors if any depth is anything but 1
+/// 4. And if all that succeeds, records how big the biggest tile is
+///
+/// The sizes are (width, height, depth) for each tile, and `path` gives
+/// the path of the tile at an index, for the error message.
+///
+/// We could add other verification here as well, of course.
+fn fit_tiles_to_spaces(
+    map: &mut crate::map::Map,
+    configured: Option<(u32, u32)>,
+    sizes: &[(u32, u32, u32)],
+    path: impl Fn(usize) -> String,
+) -> Result<(), MapEngineError> {
+    let (first_width, first_height, _) = sizes[0];
+    let expected = map.size_spaces(configured, (first_width, first_height));
+
+    if let Some(i) = sizes
+        .iter()
+        .position(|&(width, height, _)| !map.tile_fits(width, height))
+    {
+        return Err(MapEngineError::SizeMismatch {
+            path: path(i),
+            expected,
+            found: (sizes[i].0, sizes[i].1),
+        });
+    }
+    if let Some(i) = sizes.iter().position(|&(_, _, depth)| depth != 1) {
+        return Err(MapEngineError::NotTwoDimensional { path: path(i) });
+    }
+
+    for &(width, height, _) in sizes.iter() {
+        map.note_tile_size(width, height);
+    }
+    Ok(())
+}
+
+/// Cut a loaded sheet into tile textures and store them in the texture
+/// assets, returning the (strong) handles to them. If the tiles are
+/// already there, as when a sheet is reloaded, they're replaced in place.
+fn cut_tile_sheet(
+    sheet: &crate::TileSheet,
+    sheet_handle: &Handle<Texture>,
+    textures: &mut Assets<Texture>,
+) -> Result<Vec<Handle<Texture>>, MapEngineError> {
+    let sheet_texture = textures.get(sheet_handle).unwrap();
+    // The layout given (tile size and count, margin, spacing, offset)
+    // must account for every pixel of the sheet. If it doesn't, the
+    // layout is wrong, and we'd cut the tiles in the wrong places.
+    let (expected_width, expected_height) = sheet.expected_size();
+    if sheet_texture.size.width != expected_width || sheet_texture.size.height != expected_height {
+        return Err(MapEngineError::SizeMismatch {
+            path: sheet.path.clone(),
+            expected: (expected_width, expected_height),
+            found: (sheet_texture.size.width, sheet_texture.size.height),
+        });
+    }
+    if sheet_texture.size.depth != 1 {
+        return Err(MapEngineError::NotTwoDimensional {
+            path: sheet.path.clone(),
+        });
+    }
+    let tiles = crate::tile_sheet::slice_tile_sheet(sheet, sheet_texture);
+    Ok(tiles
+        .into_iter()
+        .enumerate()
+        .map(|(index, tile)| textures.set(sheet.tile_path(index as u32).as_str(), tile))
+        .collect())
+}
+
+/// Make a TileRegistry with every loaded tile in it, using the names and
+/// ids from the (already checked) tileset manifest where there are some.
+/// Names which can't be given out (see TileRegistry) come back as errors.
+fn build_registry(
+    tilehandles: &MapEngineTileHandles,
+    asset_server: &AssetServer,
+    tileset: &crate::tile_manifest::Tileset,
+) -> (crate::tile_registry::TileRegistry, Vec<MapEngineError>) {
+    let mut paths = tilehandles
+        .handles
+        .iter()
+        .map(|handle| {
+            (
+                tilehandles.path(asset_server, handle.id),
+                handle.clone().typed::<Texture>(),
+            )
+        })
+        .collect::<Vec<(String, Handle<Texture>)>>();
+    // Sorting means unlisted tiles get the same ids every time
+    paths.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut problems = Vec::new();
+    let mut next_id = tileset.iter().map(|info| info.id + 1).max().unwrap_or(0);
+    let mut registry = crate::tile_registry::TileRegistry::default();
+
+    // First, every tile gets its id and its full path, which can't clash.
+    for (path, handle) in paths.iter() {
+        match tileset.get_by_handle(handle) {
+            Some(info) => registry.insert(info.id, handle.clone()),
+            None => {
+                registry.insert(next_id, handle.clone());
+                next_id += 1;
+            }
+        }
+        if let Err(error) = registry.add_name(path, handle) {
+            problems.push(error);
+        }
+    }
+
+    // Then the manifest names, so that they win over file stems.
+    for info in tileset.iter() {
+        if let Err(error) = registry.add_name(&info.name, &info.handle) {
+            problems.push(error);
+        }
+    }
+
+    // And last, the stems. A stem shared by more than one tile doesn't
+    // go to any of them. (A BTreeMap keeps the reports in order.)
+    let mut stems: BTreeMap<String, Vec<&Handle<Texture>>> = BTreeMap::new();
+    for (path, handle) in paths.iter() {
+        stems
+            .entry(crate::tile_registry::tile_stem(path))
+            .or_default()
+            .push(handle);
+    }
+    for (stem, handles) in stems {
+        if handles.len() > 1 {
+            problems.push(MapEngineError::DuplicateTile { name: stem });
+        } else if let Err(error) = registry.add_name(&stem, handles[0]) {
+            problems.push(error);
+        }
+    }
+
+    (registry, problems)
+}
+
+/// Match each tile in the manifest up with a loaded tile texture,
+/// making sure that the tile actually exists and that names and ids
+/// are unique. Tiles which loaded but aren't in the manifest are fine;
+/// they just don't get a name or properties.
+fn build_tileset(
+    manifest: &crate::tile_manifest::TileManifest,
+    handles: &[HandleUntyped],
+) -> Result<crate::tile_manifest::Tileset, String> {
+    let mut tileset = crate::tile_manifest::Tileset::default();
+    for description in manifest.tiles.iter() {
+        // Handle ids come from the asset path, so this finds the tile
+        // no matter whether it came from the folder or a sheet.
+        let id = HandleId::from(description.path.as_str());
+        let handle = handles
+            .iter()
+            .find(|handle| handle.id == id)
+            .ok_or_else(|| {
+                format!(
+                    "tile {:?} uses {:?}, which isn't a loaded tile",
+                    description.name, description.path
+                )
+            })?;
+        tileset.insert(crate::tile_manifest::TileInfo {
+            name: description.name.clone(),
+            id: description.id,
+            path: description.path.clone(),
+            handle: handle.clone().typed::<Texture>(),
+            properties: description.properties.clone(),
+        })?;
+    }
+    Ok(tileset)
+}
+
+/// This system runs in the Running state, and watches for tile images
+/// being changed on disk. (That only happens if the asset server is
+/// watching for changes; see MapEngineConfig::with_hot_reload.)
+///
+/// When a tile changes, its size is checked again, and then every
+/// MapSpace using it is marked with MapSpaceRefreshNeeded so that
+/// maptexture_update_system redraws it. The same goes for a texture
+/// which was missing (and so drawn as a placeholder) and has now
+/// turned up. When a tile sheet changes,
+/// it's cut up again; replacing the tiles sends Modified events for
+/// each of them, which we pick up here on the next frame. Changed
+/// tiles are converted to the map's pixel format if need be, too.
+#[allow(clippy::too_many_arguments)]
+pub fn tile_reload_system(
+    commands: &mut Commands,
+    mut texture_event_reader: Local<EventReader<AssetEvent<Texture>>>,
+    texture_events: Res<Events<AssetEvent<Texture>>>,
+    tilehandles: Res<MapEngineTileHandles>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut mapengine_map: ResMut<crate::map::Map>,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<MapEngineErrors>,
+    mut error_events: ResMut<Events<MapEngineError>>,
+    mapspaces: Query<(Entity, &crate::map_space::MapSpace)>,
+) {
+    for event in texture_event_reader.iter(&texture_events) {
+        let (handle, was_missing) = match event {
+            AssetEvent::Modified { handle } => (handle, false),
+            // New textures only matter if we've been waiting for them
+            AssetEvent::Created { handle } => {
+                if !mapengine_map.missing_tiles.remove(&handle.id) {
+                    continue;
+                }
+                (handle, true)
+            }
+            _ => continue,
+        };
+
+        // Is it a whole sheet?
+        if let Some((sheet, sheet_handle)) = tilehandles
+            .sheets
+            .iter()
+            .find(|(_sheet, sheet_handle)| sheet_handle == handle)
+        {
+            if let Err(error) = cut_tile_sheet(sheet, sheet_handle, &mut textures) {
+                crate::error::report_error(error, &mut errors, &mut error_events);
+            }
+            continue;
+        }
+
+        // Otherwise, we only care about tiles, or textures we've been
+        // missing. (This also skips the map texture itself.)
+        if !was_missing
+            && !tilehandles
+                .handles
+                .iter()
+                .any(|tilehandle| tilehandle.id == handle.id)
+        {
+            continue;
+        }
+        // A new version might be in a different pixel format. If so,
+        // convert it, and carry on with the converted version right away.
+        // (Converting counts as modifying it again, so a tile will be
+        // redrawn once more next frame — but a texture which was missing
+        // wouldn't be, since that second event isn't a Created one, so we
+        // can't leave the rest until then.) If it can't be converted, it
+        // can't be drawn, so the placeholder goes in instead, as below.
+        match textures.get(handle) {
+            Some(texture) if texture.format != crate::tile_format::MAP_FORMAT => {
+                let path = tilehandles.path(&asset_server, handle.id);
+                if let Err(error) = crate::tile_format::convert_to_map_format(
+                    textures.get_mut(handle).unwrap(),
+                    &path,
+                ) {
+                    crate::error::report_error(error, &mut errors, &mut error_events);
+                    replace_with_placeholder(handle, &mut textures, &mapengine_map);
+                    continue;
+                }
+            }
+            Some(_) => (),
+            None => continue,
+        }
+        let texture = textures.get(handle).unwrap();
+
+        // The new version has to fit, just like the original did. If it doesn't,
+        // report that. The bad version is already in the texture assets under
+        // the same handle, though, so anything redrawn would use it — swap in
+        // the missing tile placeholder instead. That counts as modifying it
+        // again, so we'll be back here next frame to redraw with it. Fixing
+        // the file on disk replaces the placeholder in the same way.
+        let found = (texture.size.width, texture.size.height);
+        let problem = if !mapengine_map.tile_fits(found.0, found.1) {
+            Some(MapEngineError::SizeMismatch {
+                path: tilehandles.path(&asset_server, handle.id),
+                expected: (
+                    mapengine_map.space_width_pixels as u32,
+                    mapengine_map.space_height_pixels as u32,
+                ),
+                found,
+            })
+        } else if texture.size.depth != 1 {
+            Some(MapEngineError::NotTwoDimensional {
+                path: tilehandles.path(&asset_server, handle.id),
+            })
+        } else {
+            None
+        };
+        if let Some(error) = problem {
+            crate::error::report_error(error, &mut errors, &mut error_events);
+            replace_with_placeholder(handle, &mut textures, &mapengine_map);
+            continue;
+        }
+
+        // It might overhang further than any tile before.
+        mapengine_map.note_tile_size(found.0, found.1);
+
+        println!(
+            "Tile {} changed; redrawing.",
+            tilehandles.path(&asset_server, handle.id)
+        );
+        for (entity, mapspace) in mapspaces.iter() {
+            if mapspace.uses(handle) {
+                commands.insert_one(entity, crate::map_space::MapSpaceRefreshNeeded);
+            }
+        }
+    }
+}
+
+/// Put a copy of the missing tile placeholder in place of a tile which
+/// can't be drawn, under the same handle, so that the spaces using it
+/// show that something is wrong rather than drawing it anyway.
+fn replace_with_placeholder(
+    handle: &Handle<Texture>,
+    textures: &mut Assets<Texture>,
+    mapengine_map: &crate::map::Map,
+) {
+    if let Some(placeholder) = textures.get(&mapengine_map.missing_tile).cloned() {
+        textures.set(handle.id, placeholder);
+    }
+}
+
+/*----------------------------------------------------------------------------*/
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(i: usize) -> String {
+        format!("tile{}.png", i)
+    }
+
+    #[test]
+    fn spaces_are_sized_from_a_non_square_first_tile() {
+        let mut map = crate::map::Map::default();
+        let sizes = [(32, 16, 1), (32, 16, 1), (32, 16, 1)];
+        assert_eq!(fit_tiles_to_spaces(&mut map, None, &sizes, path), Ok(()));
+        assert_eq!(map.space_size(), (32, 16));
+    }
+
+    #[test]
+    fn a_tile_turned_on_its_side_doesnt_fit() {
+        let mut map = crate::map::Map::default();
+        let sizes = [(32, 16, 1), (16, 32, 1)];
+        assert_eq!(
+            fit_tiles_to_spaces(&mut map, None, &sizes, path),
+            Err(MapEngineError::SizeMismatch {
+                path: "tile1.png".to_string(),
+                expected: (32, 16),
+                found: (16, 32),
+            })
+        );
+    }
+
+    #[test]
+    fn configured_spaces_take_bigger_tiles_but_not_smaller() {
+        let mut map = crate::map::Map::default();
+        let sizes = [(32, 48, 1), (32, 16, 1)];
+        assert_eq!(
+            fit_tiles_to_spaces(&mut map, Some((32, 16)), &sizes, path),
+            Ok(())
+        );
+        assert_eq!(map.space_size(), (32, 16));
+
+        let mut map = crate::map::Map::default();
+        let sizes = [(32, 16, 1), (32, 8, 1)];
+        assert_eq!(
+            fit_tiles_to_spaces(&mut map, Some((32, 16)), &sizes, path),
+            Err(MapEngineError::SizeMismatch {
+                path: "tile1.png".to_string(),
+                expected: (32, 16),
+                found: (32, 8),
+            })
+        );
+    }
+
+    #[test]
+    fn tiles_must_be_flat() {
+        let mut map = crate::map::Map::default();
+        let sizes = [(32, 16, 1), (32, 16, 2)];
+        assert_eq!(
+            fit_tiles_to_spaces(&mut map, None, &sizes, path),
+            Err(MapEngineError::NotTwoDimensional {
+                path: "tile1.png".to_string(),
+            })
+        );
+    }
+}